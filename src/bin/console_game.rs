@@ -85,9 +85,10 @@ fn print_board(board: & chess::Board)
 
 fn main()
 {
-	println!("Enter the name of the destination square (e.g. `e4`),");
+	println!("Enter your move in algebraic notation (e.g. `Nf3`),");
+	println!("the name of the destination square (e.g. `e4`),");
 	print!("or both the departure the destination square of your move");
-	println!("(e.g. `e2 e4`).");
+	println!(" (e.g. `e2 e4`).");
 	println!();
 
 	/* Create a new chess game */
@@ -110,8 +111,8 @@ fn main()
 		let _ = std::io::stdin().read_line(& mut input);
 
 		let mut input_tokens = input.split_whitespace();
-		let mut from = (|t| { chess::Loc::parse(t?) })
-			(input_tokens.next());
+		let first = input_tokens.next();
+		let mut from = (|t| { chess::Loc::parse(t?) })(first);
 		let mut to = (|t| { chess::Loc::parse(t?) })
 			(input_tokens.next());
 
@@ -121,9 +122,24 @@ fn main()
 			from = None;
 		}
 
-		let moves = game.get_moves(from, to);
+		let mut moves = game.get_moves(from, to);
 		let m: & chess::Move;
 
+		/* Input that isn't a square is a move in algebraic notation */
+		if let (Some(t), None) = (first, to)
+		{
+			match game.board().parse_san(t)
+			{
+				Ok(san_move) => moves = vec![san_move],
+				Err(e) =>
+				{
+					println!("{}: {}. Try again.", t, e);
+
+					continue;
+				},
+			}
+		}
+
 		if moves.len() == 0
 		{
 			println!("No matching move was found. Try again.");
@@ -142,9 +158,8 @@ fn main()
 			{	
 				println!("{}. {}",
 					i + 1,
-					(moves[i].notation_fn)(& moves[i],
-							& game.board(),
-					& moves));
+					moves[i].notation(& game.board(),
+							& moves));
 			}
 
 			print!("Pick one: ");
@@ -0,0 +1,263 @@
+extern crate chess;
+
+use chess::{Board, Epd, Move, MovePicker};
+use chess::{KING, QUEEN, ROOK, BISHOP, KNIGHT, PAWN};
+
+const MATE: i32 = 1_000_000;
+
+/* The material of the player who has the turn, minus that of the opponent */
+fn material(b: & Board) -> i32
+{
+	let values = [(& QUEEN, 900), (& ROOK, 500), (& BISHOP, 330),
+		(& KNIGHT, 320), (& PAWN, 100), (& KING, 0)];
+
+	let count = |player, kind| b.bitboard(player, kind).count_ones() as i32;
+
+	values.iter().map(|(kind, value)| value
+		* (count(b.player(), kind) - count(b.player().opponent(), kind)))
+		.sum()
+}
+
+/* A plain alpha-beta search of material, to score the best move suites */
+fn alpha_beta(b: & mut Board, depth: u32, mut alpha: i32, beta: i32) -> i32
+{
+	if depth == 0
+	{
+		return material(b);
+	}
+
+	let mut legal = false;
+
+	for m in MovePicker::new(b, None, & [])
+	{
+		legal = true;
+
+		let undo = b.make_move(& m);
+		let score = -alpha_beta(b, depth - 1, -beta, -alpha);
+
+		b.unmake_move(& m, & undo);
+
+		if score >= beta
+		{
+			return beta;
+		}
+
+		alpha = alpha.max(score);
+	}
+
+	/* Prefer the quickest mate, which leaves the most depth */
+	match legal
+	{
+		true => alpha,
+		false if b.is_check(b.player()) => -MATE - depth as i32,
+		false => 0,
+	}
+}
+
+fn search(board: & Board, depth: u32) -> Option<Move>
+{
+	let mut b = *board;
+	let mut best: Option<(Move, i32)> = None;
+
+	for m in MovePicker::new(board, None, & [])
+	{
+		let alpha = best.map_or(-2 * MATE, |(_, s)| s);
+
+		let undo = b.make_move(& m);
+		let score = -alpha_beta(& mut b, depth.max(1) - 1, -2 * MATE,
+			-alpha);
+
+		b.unmake_move(& m, & undo);
+
+		if best.is_none_or(|(_, s)| score > s)
+		{
+			best = Some((m, score));
+		}
+	}
+
+	best.map(|(m, _)| m)
+}
+
+#[derive(Copy, Clone, Default)]
+struct Summary
+{
+	passed	: u32,
+	failed	: u32,
+}
+
+fn name(epd: & Epd, line: usize) -> String
+{
+	epd.id().map_or(format!("line {}", line), String::from)
+}
+
+/* Check the perft counts of the record up to the maximum depth */
+fn run_perft(epd: & Epd, line: usize, max_depth: u32,
+		table: & chess::PerftTable, summary: & mut Summary)
+{
+	let threads = std::thread::available_parallelism()
+		.map_or(1, |n| n.get());
+
+	for (depth, expected) in epd.perft_counts()
+	{
+		if depth > max_depth
+		{
+			continue;
+		}
+
+		let nodes = epd.board.parallel_perft(depth, threads, Some(table));
+
+		if nodes == expected
+		{
+			summary.passed += 1;
+		}
+		else
+		{
+			summary.failed += 1;
+
+			println!("FAIL {} D{}: expected {}, counted {}",
+				name(epd, line), depth, expected, nodes);
+		}
+	}
+}
+
+/* Check the move found by the search against the bm and am operations */
+fn run_search(epd: & Epd, line: usize, depth: u32, summary: & mut Summary)
+{
+	let m = match search(& epd.board, depth)
+	{
+		Some(m) => m,
+		None =>
+		{
+			summary.failed += 1;
+
+			println!("FAIL {}: no legal moves", name(epd, line));
+			return;
+		},
+	};
+
+	let b = & epd.board;
+	let notation = m.notation(b, & b.moves(b.player()));
+
+	match epd.solved_by(& m)
+	{
+		Ok(true) =>
+		{
+			summary.passed += 1;
+
+			println!("pass {}: {}", name(epd, line), notation);
+		},
+		Ok(false) =>
+		{
+			summary.failed += 1;
+
+			println!("FAIL {}: {}", name(epd, line), notation);
+		},
+		/* A record with a move that cannot be read is counted as a
+		 * failure, so that a broken suite does not pass */
+		Err(e) =>
+		{
+			summary.failed += 1;
+
+			println!("error {}: {}", name(epd, line), e);
+		},
+	}
+}
+
+fn usage() -> !
+{
+	eprintln!("usage: epd <file> [--perft-depth n] [--search-depth n]");
+	std::process::exit(1);
+}
+
+fn main()
+{
+	let args = std::env::args().skip(1).collect::<Vec<String>>();
+
+	let mut path = None;
+	let mut perft_depth = 6;
+	let mut search_depth = 3;
+
+	let mut i = 0;
+
+	while i < args.len()
+	{
+		let value = || args.get(i + 1)
+			.and_then(|s| s.parse::<u32>().ok())
+			.unwrap_or_else(|| usage());
+
+		match args[i].as_str()
+		{
+			"--perft-depth" => { perft_depth = value(); i += 1; },
+			"--search-depth" => { search_depth = value(); i += 1; },
+			s if path.is_none() => path = Some(String::from(s)),
+			_ => usage(),
+		}
+
+		i += 1;
+	}
+
+	let path = path.unwrap_or_else(|| usage());
+
+	let text = match std::fs::read_to_string(path)
+	{
+		Ok(text) => text,
+		Err(e) =>
+		{
+			eprintln!("{}", e);
+			std::process::exit(1);
+		},
+	};
+
+	let table = chess::PerftTable::new(1 << 20);
+	let mut perft = Summary::default();
+	let mut moves = Summary::default();
+	let mut errors = 0;
+
+	for (i, line) in text.lines().enumerate()
+	{
+		if line.trim().is_empty() || line.starts_with('#')
+		{
+			continue;
+		}
+
+		let epd = match Epd::parse(line)
+		{
+			Ok(epd) => epd,
+			Err(e) =>
+			{
+				errors += 1;
+
+				println!("error line {}: {}", i + 1, e);
+				continue;
+			},
+		};
+
+		if epd.operation("bm").is_some() || epd.operation("am").is_some()
+		{
+			run_search(& epd, i + 1, search_depth, & mut moves);
+		}
+		else
+		{
+			run_perft(& epd, i + 1, perft_depth, & table, & mut perft);
+		}
+	}
+
+	for (kind, s) in [("perft", perft), ("best move", moves)]
+	{
+		if s.passed + s.failed > 0
+		{
+			println!("{}: {} passed, {} failed", kind, s.passed, s.failed);
+		}
+	}
+
+	if errors > 0
+	{
+		println!("unparsed records: {}", errors);
+	}
+
+	/* Signal failures to scripts */
+	if perft.failed + moves.failed + errors > 0
+	{
+		std::process::exit(1);
+	}
+}
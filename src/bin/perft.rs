@@ -0,0 +1,76 @@
+extern crate chess;
+
+fn usage() -> !
+{
+	eprintln!("usage: perft <depth> [fen]");
+	std::process::exit(1);
+}
+
+fn main()
+{
+	let args = std::env::args().skip(1).collect::<Vec<String>>();
+
+	let depth = match args.first().map(|s| s.parse::<u32>())
+	{
+		Some(Ok(depth)) => depth,
+		_ => usage(),
+	};
+
+	/* The FEN may be given as a single argument or as one argument per
+	 * field */
+	let board = if args.len() > 1
+	{
+		match chess::Board::from_fen(& args[1..].join(" "))
+		{
+			Ok(board) => board,
+			Err(e) =>
+			{
+				eprintln!("invalid FEN: {}", e);
+				std::process::exit(1);
+			},
+		}
+	}
+	else
+	{
+		chess::Board::default()
+	};
+
+	/* Build the attack tables, which happens on the first slider attack
+	 * lookup, before starting the clock */
+	board.moves(board.player());
+
+	let start = std::time::Instant::now();
+	let mut total = chess::PerftStats::default();
+
+	if depth == 0
+	{
+		total.nodes = 1;
+	}
+	else
+	{
+		let mut divide = board.perft_divide(depth);
+
+		divide.sort_by_key(|(m, _)| m.uci());
+
+		for (m, stats) in & divide
+		{
+			println!("{}: {}", m.uci(), stats.nodes);
+
+			total.add(stats);
+		}
+
+		println!();
+	}
+
+	let time = start.elapsed().as_secs_f64();
+
+	println!("Nodes:      {}", total.nodes);
+	println!("Captures:   {}", total.captures);
+	println!("E.p.:       {}", total.passants);
+	println!("Castles:    {}", total.castles);
+	println!("Promotions: {}", total.promotions);
+	println!("Checks:     {}", total.checks);
+	println!("Checkmates: {}", total.mates);
+	println!("Time:       {:.3} s", time);
+	println!("NPS:        {:.0}", total.nodes as f64 / time.max(1e-9));
+}
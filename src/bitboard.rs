@@ -0,0 +1,375 @@
+use crate::board::*;
+use crate::player::*;
+
+/* A bitboard has one bit for each square of the board, with bit
+ * (y * 8 + x) representing the square at location (x, y). */
+
+pub fn loc_bit(loc: Loc) -> u64
+{
+	1 << (loc.y * 8 + loc.x)
+}
+
+pub fn bit_loc(i: u32) -> Loc
+{
+	Loc
+	{
+		x: (i % 8) as i32,
+		y: (i / 8) as i32,
+	}
+}
+
+fn loc_index(loc: Loc) -> usize
+{
+	(loc.y * 8 + loc.x) as usize
+}
+
+/* Iterate over the locations of the set bits of a bitboard */
+#[derive(Copy, Clone)]
+pub struct Bits(pub u64);
+
+impl Iterator for Bits
+{
+	type Item = Loc;
+
+	fn next(& mut self) -> Option<Loc>
+	{
+		if self.0 == 0
+		{
+			None
+		}
+		else
+		{
+			let i = self.0.trailing_zeros();
+
+			/* Clear the lowest set bit */
+			self.0 &= self.0 - 1;
+
+			Some(bit_loc(i))
+		}
+	}
+}
+
+const KING_DIRS: [(i32, i32); 8] =
+[
+	(-1,  1), ( 0,  1), ( 1,  1),
+	(-1,  0),           ( 1,  0),
+	(-1, -1), ( 0, -1), ( 1, -1),
+];
+
+const KNIGHT_DIRS: [(i32, i32); 8] =
+[
+	(-2,  1), (-1,  2), ( 1,  2), ( 2,  1),
+	(-2, -1), (-1, -2), ( 1, -2), ( 2, -1),
+];
+
+const WHITE_PAWN_DIRS: [(i32, i32); 2] = [(-1,  1), ( 1,  1)];
+const BLACK_PAWN_DIRS: [(i32, i32); 2] = [(-1, -1), ( 1, -1)];
+
+const DIAGONAL_DIRS: [(i32, i32); 4] =
+	[(-1,  1), (-1, -1), ( 1,  1), ( 1, -1)];
+const CARDINAL_DIRS: [(i32, i32); 4] =
+	[(-1,  0), ( 0,  1), ( 0, -1), ( 1,  0)];
+
+/* Build a table of the squares attacked from each square by a piece that
+ * steps once in each of the given directions. Evaluated at compile
+ * time. */
+const fn step_table(dirs: & [(i32, i32)]) -> [u64; 64]
+{
+	let mut table = [0; 64];
+	let mut i = 0;
+
+	while i < 64
+	{
+		let mut j = 0;
+
+		while j < dirs.len()
+		{
+			let x = (i % 8) as i32 + dirs[j].0;
+			let y = (i / 8) as i32 + dirs[j].1;
+
+			if x >= 0 && x < 8 && y >= 0 && y < 8
+			{
+				table[i] |= 1 << (y * 8 + x);
+			}
+
+			j += 1;
+		}
+
+		i += 1;
+	}
+
+	table
+}
+
+static KING_ATTACKS: [u64; 64] = step_table(& KING_DIRS);
+static KNIGHT_ATTACKS: [u64; 64] = step_table(& KNIGHT_DIRS);
+static PAWN_ATTACKS: [[u64; 64]; 2] =
+[
+	step_table(& WHITE_PAWN_DIRS),
+	step_table(& BLACK_PAWN_DIRS),
+];
+
+pub fn king_attacks(loc: Loc) -> u64
+{
+	KING_ATTACKS[loc_index(loc)]
+}
+
+pub fn knight_attacks(loc: Loc) -> u64
+{
+	KNIGHT_ATTACKS[loc_index(loc)]
+}
+
+/* Squares attacked by a pawn of the given player */
+pub fn pawn_attacks(player: Player, loc: Loc) -> u64
+{
+	PAWN_ATTACKS[player as usize][loc_index(loc)]
+}
+
+/* Squares attacked by sliding along the given directions, up to and
+ * including the first occupied square in each direction. Only used to
+ * build the magic tables. */
+fn ray_attacks(loc: Loc, occupied: u64, dirs: & [(i32, i32)]) -> u64
+{
+	let mut bb = 0;
+
+	for dir in dirs
+	{
+		let mut to = loc.offset(*dir);
+
+		while to.valid()
+		{
+			bb |= loc_bit(to);
+
+			if occupied & loc_bit(to) != 0
+			{
+				break;
+			}
+
+			to = to.offset(*dir);
+		}
+	}
+
+	bb
+}
+
+/* The squares whose occupancy matters to a slider on the given location.
+ * The last square of each ray is left out, since it is attacked whether or
+ * not it is occupied. */
+fn relevant_mask(loc: Loc, dirs: & [(i32, i32)]) -> u64
+{
+	let mut bb = 0;
+
+	for dir in dirs
+	{
+		let mut to = loc.offset(*dir);
+
+		while to.offset(*dir).valid()
+		{
+			bb |= loc_bit(to);
+
+			to = to.offset(*dir);
+		}
+	}
+
+	bb
+}
+
+/* Multiplying the relevant occupancy of a square by its magic number maps
+ * every occupancy to a distinct index (or one with the same attacks) in
+ * the high bits of the product. */
+#[derive(Copy, Clone)]
+struct Magic
+{
+	mask	: u64,
+	magic	: u64,
+	shift	: u32,
+	offset	: usize,
+}
+
+impl Magic
+{
+	fn index(self: & Self, occupied: u64) -> usize
+	{
+		self.offset
+			+ ((occupied & self.mask).wrapping_mul(self.magic)
+				>> self.shift) as usize
+	}
+}
+
+struct MagicTables
+{
+	diagonal	: [Magic; 64],
+	cardinal	: [Magic; 64],
+	attacks		: Vec<u64>,
+}
+
+/* The magic numbers and shifts of the squares, found once by trying sparse
+ * random numbers until every occupancy of the relevant squares mapped to an
+ * index with the right attacks. The shift leaves as many bits as there are
+ * relevant squares. */
+const DIAGONAL_MAGICS: [(u64, u32); 64] =
+[
+	(0x8008029802002200, 58), (0x4202100102008801, 59),
+	(0x0041020881024000, 59), (0x2418061040804001, 59),
+	(0x0804504004140804, 59), (0x0200829040030010, 59),
+	(0x0000809008200200, 59), (0x0002044208040200, 58),
+	(0x0001911001010c01, 59), (0x0200200404006840, 59),
+	(0x05009001284110c0, 59), (0x7150680a410a0004, 59),
+	(0x0002940421001011, 59), (0x0002020202200002, 59),
+	(0x0004008208224282, 59), (0x2010004208010801, 59),
+	(0x0040004504040c00, 59), (0x0109002002008200, 59),
+	(0x0008801004220020, 57), (0x844c802802024027, 57),
+	(0xc0120024010c0200, 57), (0x0812080100a00401, 57),
+	(0x0014241044220800, 59), (0x00824102020a0184, 59),
+	(0x0004208040088100, 59), (0x0021042008080800, 59),
+	(0x4200404104050208, 57), (0x0048a00804010020, 55),
+	(0x0007840000802000, 55), (0xd804081001010080, 57),
+	(0x10184b100a008208, 59), (0x0004028c00221100, 59),
+	(0x4801049120c19000, 59), (0x0440900804040840, 59),
+	(0x6084020a48080080, 57), (0x0882404800248200, 55),
+	(0x0084210010040040, 55), (0x0410100282004040, 57),
+	(0x8002040840040210, 59), (0x3001040485802220, 59),
+	(0x0005080240007000, 59), (0x80010402020c2008, 59),
+	(0x0140104138001000, 57), (0x0200404200802808, 57),
+	(0x082102a00a006100, 57), (0xa0a02004014284a0, 57),
+	(0x0411440080800400, 59), (0x2011181083060b80, 59),
+	(0x2804010802900040, 59), (0x0000804442200010, 59),
+	(0xc000050098440008, 59), (0x001b4000c2088000, 59),
+	(0x000200b010504220, 59), (0x0003404801010400, 59),
+	(0x1808081004084120, 59), (0xb020111402908050, 59),
+	(0x4202088088180201, 58), (0x0500428201412030, 59),
+	(0x0202000212010404, 59), (0x0000008400420200, 59),
+	(0x00300c4040250110, 59), (0x0b20008810500080, 59),
+	(0x4a08041002420410, 59), (0x8058200804604082, 58),
+];
+
+const CARDINAL_MAGICS: [(u64, u32); 64] =
+[
+	(0x2080002080400010, 52), (0x00c0002001401000, 53),
+	(0x2100110008402002, 53), (0x0880080081041000, 53),
+	(0x0200020020041008, 53), (0x2300040008010012, 53),
+	(0x0c00283004008201, 53), (0x0180010000407a80, 52),
+	(0x0168800080400020, 53), (0x0010400040201000, 54),
+	(0x1001002001001048, 54), (0x1001002408100100, 54),
+	(0x0801000408010012, 54), (0x4001000209000400, 54),
+	(0x08a20004c8020001, 54), (0x2002801145002280, 53),
+	(0x0080860021004200, 53), (0x001000c009402002, 54),
+	(0x0010002008002400, 54), (0x100a808010020800, 54),
+	(0x8101010008000410, 54), (0x0244008002000480, 54),
+	(0x0000040010810208, 54), (0x2000020000448534, 53),
+	(0x4104400480008033, 53), (0x0000810100204000, 54),
+	(0x0440430900200010, 54), (0x0002090100100420, 54),
+	(0x4400080100041100, 54), (0x0001000300080400, 54),
+	(0x0004084400011002, 54), (0x0023040200008041, 53),
+	(0x0580050043002080, 53), (0x0400804002802008, 54),
+	(0x0001002001004010, 54), (0x1000200901001000, 54),
+	(0x0000080080800400, 54), (0x5404800200800401, 54),
+	(0x2000020804000150, 54), (0x0800008402000041, 53),
+	(0x0180002000504000, 53), (0x0070002000424008, 54),
+	(0x1040408012020020, 54), (0x8010040008004040, 54),
+	(0x2001080100110004, 54), (0x0000020004008080, 54),
+	(0x0021010810040002, 54), (0x0800008c43020024, 53),
+	(0x0000800021005100, 53), (0x0080984000210100, 54),
+	(0x0401220144108200, 54), (0x6008018210030880, 54),
+	(0x0884000800800480, 54), (0x0005220080640080, 54),
+	(0x0482011002080400, 54), (0x2000210044008200, 53),
+	(0x8208210040120882, 52), (0x0040008100102049, 53),
+	(0x0042102082000a42, 53), (0xc401210810000501, 53),
+	(0x0241001002480005, 53), (0x0081000400880241, 53),
+	(0x0000009008024124, 53), (0x0048122980410402, 52),
+];
+
+/* Fill the attack table of the given square, using its magic number, and
+ * append it to the attacks */
+fn fill_magic(loc: Loc, dirs: & [(i32, i32)], (magic, shift): (u64, u32),
+		attacks: & mut Vec<u64>) -> Magic
+{
+	let magic = Magic
+	{
+		mask	: relevant_mask(loc, dirs),
+		magic,
+		shift,
+		offset	: attacks.len(),
+	};
+
+	attacks.resize(attacks.len() + (1 << (64 - shift)), 0);
+
+	/* Enumerate every subset of the mask, along with its attacks */
+	let mut occupied: u64 = 0;
+
+	loop
+	{
+		attacks[magic.index(occupied)] = ray_attacks(loc, occupied, dirs);
+
+		occupied = occupied.wrapping_sub(magic.mask) & magic.mask;
+
+		if occupied == 0
+		{
+			break;
+		}
+	}
+
+	magic
+}
+
+/* The tables are built the first time a slider attack is looked up */
+fn magic_tables() -> & 'static MagicTables
+{
+	static TABLES: std::sync::OnceLock<MagicTables> =
+		std::sync::OnceLock::new();
+
+	TABLES.get_or_init(||
+	{
+		let mut attacks = Vec::<u64>::new();
+
+		let empty = Magic { mask: 0, magic: 0, shift: 0, offset: 0 };
+		let mut diagonal = [empty; 64];
+		let mut cardinal = [empty; 64];
+
+		for i in 0..64
+		{
+			let loc = bit_loc(i as u32);
+
+			diagonal[i] = fill_magic(loc, & DIAGONAL_DIRS,
+						DIAGONAL_MAGICS[i], & mut attacks);
+			cardinal[i] = fill_magic(loc, & CARDINAL_DIRS,
+						CARDINAL_MAGICS[i], & mut attacks);
+		}
+
+		MagicTables { diagonal, cardinal, attacks }
+	})
+}
+
+pub fn diagonal_attacks(loc: Loc, occupied: u64) -> u64
+{
+	let t = magic_tables();
+
+	t.attacks[t.diagonal[loc_index(loc)].index(occupied)]
+}
+
+pub fn cardinal_attacks(loc: Loc, occupied: u64) -> u64
+{
+	let t = magic_tables();
+
+	t.attacks[t.cardinal[loc_index(loc)].index(occupied)]
+}
+
+/* The squares strictly between two locations on the same rank, file or
+ * diagonal, or none if they are not on a line */
+pub fn between(a: Loc, b: Loc) -> u64
+{
+	let (bit_a, bit_b) = (loc_bit(a), loc_bit(b));
+
+	if cardinal_attacks(a, 0) & bit_b != 0
+	{
+		cardinal_attacks(a, bit_b) & cardinal_attacks(b, bit_a)
+	}
+	else if diagonal_attacks(a, 0) & bit_b != 0
+	{
+		diagonal_attacks(a, bit_b) & diagonal_attacks(b, bit_a)
+	}
+	else
+	{
+		0
+	}
+}
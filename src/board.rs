@@ -1,9 +1,15 @@
+use crate::bitboard::*;
 use crate::fen::*;
 use crate::r#move::*;
+use crate::move_notation::*;
+use crate::perft::*;
 use crate::piece::*;
 use crate::player::*;
+use crate::polyglot::*;
+use crate::validate::*;
+use crate::zobrist::*;
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Loc
 {
 	pub x	: i32,
@@ -111,7 +117,7 @@ impl Loc
 }
 
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum Square
 {
 	Empty,
@@ -139,7 +145,7 @@ impl Square
 	}
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct Castling
 {
 	pub k	: Option<Loc>,
@@ -163,13 +169,73 @@ impl Castling
 	];
 }
 
+/* Return the bitboard of the given player's pieces that attack the given
+ * location, with only the given squares counted as occupied. Sliders see
+ * through the other squares, as when pieces are exchanged on the
+ * location. */
+pub fn attackers(b: & Board, loc: Loc, by: Player, occupied: u64) -> u64
+{
+	let diagonal = b.bitboard(by, & BISHOP) | b.bitboard(by, & QUEEN);
+	let cardinal = b.bitboard(by, & ROOK) | b.bitboard(by, & QUEEN);
+
+	/* A pawn attacks the location if a pawn of the other player on the
+	 * location would attack the pawn */
+	(king_attacks(loc) & b.bitboard(by, & KING)
+		| knight_attacks(loc) & b.bitboard(by, & KNIGHT)
+		| pawn_attacks(by.opponent(), loc) & b.bitboard(by, & PAWN)
+		| diagonal_attacks(loc, occupied) & diagonal
+		| cardinal_attacks(loc, occupied) & cardinal)
+		& occupied
+}
+
+/* Enumerate the moves that the given function generates for each of the
+ * given player's pieces, including illegal ones */
+pub fn generate_moves<F>(b: & Board, player: Player, f: F) -> Vec<Move>
+	where F: Fn(& Piece, Loc) -> Vec<Move>
+{
+	let mut ms = Vec::<Move>::new();
+
+	for loc in Bits(b.occupancy(player))
+	{
+		if let Square::Occupied(p) = b.at(loc)
+		{
+			ms.extend(f(p, loc));
+		}
+	}
+
+	ms
+}
+
+/* The state of a board that can not be recovered from a move, kept by
+ * Board::make_move so that the move can be unmade */
+#[derive(Copy, Clone)]
+pub struct Undo
+{
+	captured	: Square,
+	passant		: Option<Loc>,
+	castling	: [Castling; 2],
+	halfmove_clock	: i32,
+	fullmove	: i32,
+	key		: u64,
+}
+
+/* The board keeps the piece on each square, along with bitboards of the
+ * squares occupied by each kind of piece and by each player, and the
+ * Zobrist hash of the position. The squares are only changed through
+ * Board::set, which keeps the bitboards and the hash of the pieces up to
+ * date. */
 #[derive(Copy, Clone)]
 pub struct Board
 {
-	pub player	: Player,
-	pub squares	: [Square; 8 * 8],
-	pub passant	: Option<Loc>,
-	pub castling	: [Castling; 2],
+	player		: Player,
+	squares		: [Square; 8 * 8],
+	pieces		: [u64; 6],
+	colors		: [u64; 2],
+	key		: u64,
+	passant		: Option<Loc>,
+	castling	: [Castling; 2],
+	pub halfmove_clock	: i32,
+	pub fullmove	: i32,
 }
 
 impl Board
@@ -179,9 +245,104 @@ impl Board
 		& self.squares[(loc.y * 8 + loc.x) as usize]
 	}
 
-	pub fn at_mut(self: & mut Self, loc: Loc) -> & mut Square
+	/* Put the given square on the board at the given location */
+	pub fn set(self: & mut Self, loc: Loc, square: Square)
+	{
+		let bit = loc_bit(loc);
+
+		if let Square::Occupied(p) = *self.at(loc)
+		{
+			self.pieces[p.kind.index] &= !bit;
+			self.colors[p.player as usize] &= !bit;
+			self.key ^= piece_key(& p, loc);
+		}
+
+		if let Square::Occupied(p) = square
+		{
+			self.pieces[p.kind.index] |= bit;
+			self.colors[p.player as usize] |= bit;
+			self.key ^= piece_key(& p, loc);
+		}
+
+		self.squares[(loc.y * 8 + loc.x) as usize] = square;
+	}
+
+	/* Return the 64-bit Zobrist hash of the position. The hash is updated
+	 * whenever a square changes, and by Board::make_move for the player
+	 * who has the turn, the castling rights and the passant file. */
+	pub fn hash(self: & Self) -> u64
+	{
+		self.key
+	}
+
+	/* Compute the Zobrist hash of the position from scratch */
+	pub fn compute_hash(self: & Self) -> u64
+	{
+		zobrist_hash(self)
+	}
+
+	/* The keys of the state of the board besides the squares */
+	fn state_key(self: & Self) -> u64
+	{
+		let player = match self.player
+		{
+			Player::White => 0,
+			Player::Black => player_key(),
+		};
+
+		player ^ castling_key(& self.castling) ^ passant_key(self)
+	}
+
+	/* Change the state of the board besides the squares, and swap the
+	 * keys of the old state for those of the new one */
+	fn update_state<F: FnOnce(& mut Self)>(self: & mut Self, f: F)
+	{
+		let old = self.state_key();
+
+		f(self);
+
+		self.key ^= old ^ self.state_key();
+	}
+
+	pub fn player(self: & Self) -> Player
+	{
+		self.player
+	}
+
+	pub fn set_player(self: & mut Self, player: Player)
+	{
+		self.update_state(|b| b.player = player);
+	}
+
+	pub fn passant(self: & Self) -> Option<Loc>
 	{
-		& mut self.squares[(loc.y * 8 + loc.x) as usize]
+		self.passant
+	}
+
+	pub fn set_passant(self: & mut Self, passant: Option<Loc>)
+	{
+		self.update_state(|b| b.passant = passant);
+	}
+
+	/* Return the key of the position in Polyglot opening books, made
+	 * with the random numbers published with Polyglot */
+	pub fn polyglot_key(self: & Self) -> u64
+	{
+		polyglot_key(& POLYGLOT_RANDOM, self)
+	}
+
+	/* Return the bitboard of the squares occupied by the given player's
+	 * pieces of the given kind */
+	pub fn bitboard(self: & Self, player: Player, kind: & PieceKind) -> u64
+	{
+		self.pieces[kind.index] & self.colors[player as usize]
+	}
+
+	/* Return the bitboard of the squares occupied by the given player's
+	 * pieces */
+	pub fn occupancy(self: & Self, player: Player) -> u64
+	{
+		self.colors[player as usize]
 	}
 
 	pub fn locations(self: & Self) -> Loc
@@ -194,80 +355,601 @@ impl Board
 		& self.castling[player as usize]
 	}
 
-	pub fn castling_mut(self: & mut Self, player: Player) -> & mut Castling
+	pub fn set_castling(self: & mut Self, player: Player, castling: Castling)
 	{
-		& mut self.castling[player as usize]
+		self.update_state(|b| b.castling[player as usize] = castling);
 	}
 
 	/* Enumerate all possible moves according to the rules of piece
 	 * movement, including illegal moves (self-checks). */
 	fn piece_moves(self: & Self, player: Player) -> Vec::<Move>
 	{
-		let mut ms = Vec::<Move>::new();
+		generate_moves(self, player, |p, loc| p.moves(self, loc))
+	}
 
-		for loc in self.locations()
+	/* Return the locations of the given player's pieces that attack the
+	 * given location. The bitboard of the attackers is the field of the
+	 * returned Bits. */
+	pub fn attackers_to(self: & Self, loc: Loc, by: Player) -> Bits
+	{
+		let occupied = self.occupancy(Player::White)
+			| self.occupancy(Player::Black);
+
+		Bits(attackers(self, loc, by, occupied))
+	}
+
+	/* Check whether the given location is attacked by any of the given
+	 * player's pieces */
+	pub fn is_square_attacked(self: & Self, loc: Loc, by: Player) -> bool
+	{
+		self.attackers_to(loc, by).0 != 0
+	}
+
+	/* Return the bitboard of the pieces that give check to the king of
+	 * the player who has the turn */
+	pub fn checkers(self: & Self) -> u64
+	{
+		Bits(self.bitboard(self.player, & KING))
+			.fold(0, |bb, loc| bb
+				| self.attackers_to(loc, self.player.opponent()).0)
+	}
+
+	/* Return the bitboard of the given player's pieces that can not move
+	 * off the line between their king and an opponent's slider without
+	 * exposing the king */
+	pub fn pinned_pieces(self: & Self, player: Player) -> u64
+	{
+		let occupied = self.occupancy(Player::White)
+			| self.occupancy(Player::Black);
+
+		let by = player.opponent();
+		let diagonal = self.bitboard(by, & BISHOP)
+			| self.bitboard(by, & QUEEN);
+		let cardinal = self.bitboard(by, & ROOK)
+			| self.bitboard(by, & QUEEN);
+
+		let mut pinned = 0;
+
+		for king in Bits(self.bitboard(player, & KING))
 		{
-			match self.at(loc)
+			/* Sliders that would attack the king on an empty board */
+			let snipers = diagonal_attacks(king, 0) & diagonal
+				| cardinal_attacks(king, 0) & cardinal;
+
+			for sniper in Bits(snipers)
 			{
-				Square::Occupied(p) if p.player == player
-					=> ms.extend(p.moves(self, loc)),
-				_ => (),
+				let blockers = between(king, sniper) & occupied;
+
+				if blockers.count_ones() == 1
+					&& blockers & self.occupancy(player) != 0
+				{
+					pinned |= blockers;
+				}
 			}
 		}
 
-		ms
+		pinned
 	}
 
 	/* Check whether the board state is check for the given player's king
 	 */
 	pub fn is_check(self: & Self, player: Player) -> bool
+	{
+		Bits(self.bitboard(player, & KING))
+			.any(|loc| self.is_square_attacked(loc, player.opponent()))
+	}
+
+	/* Keep only legal moves, i.e. moves that do not put the moving
+	 * player's king in check. */
+	fn legal_moves(self: & Self, player: Player, mut ms: Vec<Move>)
+		-> Vec<Move>
+	{
+		let mut b = *self;
+
+		ms.retain(|m|
+		{
+			let undo = b.make_move(m);
+			let legal = !b.is_check(player);
+
+			b.unmake_move(m, & undo);
+
+			legal
+		});
+
+		ms
+	}
+
+	/* Enumerate the legal moves that the given function generates for
+	 * each of the given player's pieces */
+	fn generate<F>(self: & Self, player: Player, f: F) -> Vec<Move>
+		where F: Fn(& Piece, Loc) -> Vec<Move>
+	{
+		self.legal_moves(player, generate_moves(self, player, f))
+	}
+
+	/* Enumerate all legal moves */
+	pub fn moves(self: & Self, player: Player) -> Vec<Move>
+	{
+		self.legal_moves(player, self.piece_moves(player))
+	}
+
+	/* Enumerate the legal captures and promotions */
+	pub fn captures(self: & Self, player: Player) -> Vec<Move>
+	{
+		self.generate(player, |p, loc| capture_moves(self, p, loc))
+	}
+
+	/* Enumerate the legal moves that neither capture nor promote. Together
+	 * with the captures these are all legal moves. */
+	pub fn quiets(self: & Self, player: Player) -> Vec<Move>
+	{
+		self.generate(player, |p, loc| quiet_moves(self, p, loc))
+	}
+
+	/* Enumerate the legal moves that get the given player's king out of
+	 * check: king moves, and when there is a single checker, captures of
+	 * the checker and moves that block its line. These are all legal
+	 * moves when the player is in check, and none otherwise. */
+	pub fn evasions(self: & Self, player: Player) -> Vec<Move>
+	{
+		let king = match Bits(self.bitboard(player, & KING)).next()
+		{
+			Some(king) => king,
+			None => return Vec::new(),
+		};
+
+		let checkers = self.attackers_to(king, player.opponent()).0;
+
+		if checkers == 0
+		{
+			return Vec::new();
+		}
+
+		self.generate(player, |p, loc|
+		{
+			if p.is_kind(& KING)
+			{
+				attack_moves(self, p, loc, king_attacks(loc))
+			}
+			/* Only the king can escape a double check */
+			else if checkers.count_ones() == 1
+			{
+				let checker = bit_loc(checkers.trailing_zeros());
+
+				blocking_moves(self, p, loc, checker,
+					between(king, checker))
+			}
+			else
+			{
+				Vec::new()
+			}
+		})
+	}
+
+	/* The location of the pawn captured by a passant move */
+	fn passant_capture_loc(m: & Move) -> Loc
+	{
+		Loc { x: m.to.x, y: m.from.y }
+	}
+
+	/* The location the rook ends up on when castling, on the f file for
+	 * O-O and the d file for O-O-O, next to the king */
+	fn castle_rook_to(m: & Move, rook: Loc) -> Loc
+	{
+		if rook.x > m.from.x
+		{
+			Loc { x: 5, y: m.from.y }
+		}
+		else
+		{
+			Loc { x: 3, y: m.from.y }
+		}
+	}
+
+	/* Make the given move on the board, and return what is needed to unmake
+	 * it */
+	pub fn make_move(self: & mut Self, m: & Move) -> Undo
+	{
+		let p = m.piece(self);
+
+		let captured = if m.is_passant()
+		{
+			*self.at(Self::passant_capture_loc(m))
+		}
+		else if m.is_castle()
+		{
+			Square::Empty
+		}
+		else
+		{
+			*self.at(m.to)
+		};
+
+		let undo = Undo
+		{
+			captured,
+			passant		: self.passant,
+			castling	: self.castling,
+			halfmove_clock	: self.halfmove_clock,
+			fullmove	: self.fullmove,
+			key		: self.key,
+		};
+
+		let state = self.state_key();
+
+		self.set(m.from, Square::Empty);
+
+		if m.is_passant()
+		{
+			self.set(Self::passant_capture_loc(m), Square::Empty);
+		}
+
+		/* Remove the rook before placing the king, in case the king
+		 * ends up on the rook's square */
+		if let (Some(rook), true) = (m.rook(), m.is_castle())
+		{
+			let r = *self.at(rook);
+
+			self.set(rook, Square::Empty);
+			self.set(m.to, Square::Occupied(p));
+			self.set(Self::castle_rook_to(m, rook), r);
+		}
+		else
+		{
+			self.set(m.to, Square::Occupied(Piece
+			{
+				kind: m.promotion.unwrap_or(p.kind),
+				..p
+			}));
+		}
+
+		/* Moving the king gives up all of the player's castling rights,
+		 * and moves to or from castling squares remove the castling
+		 * rights for that square. */
+		if p.is_kind(& KING)
+		{
+			self.castling[p.player as usize] = Castling { k: None, q: None };
+		}
+
+		for c in & mut self.castling
+		{
+			for side in [& mut c.k, & mut c.q]
+			{
+				if *side == Some(m.from) || *side == Some(m.to)
+				{
+					*side = None;
+				}
+			}
+		}
+
+		/* A pawn that moves two squares allows passant on the square
+		 * it passed */
+		self.passant = None;
+
+		if m.flags.double_push
+		{
+			self.passant = Some(Loc
+			{
+				x: m.from.x,
+				y: (m.from.y + m.to.y) / 2,
+			});
+		}
+
+		/* Captures and pawn moves reset the halfmove clock */
+		if m.is_capture() || p.is_kind(& PAWN)
+		{
+			self.halfmove_clock = 0;
+		}
+		else
+		{
+			self.halfmove_clock += 1;
+		}
+
+		/* Pass the turn, and start a new fullmove after black has
+		 * moved */
+		self.player = p.player.opponent();
+
+		if p.player == Player::Black
+		{
+			self.fullmove += 1;
+		}
+
+		/* Swap the keys of the old state for those of the new one */
+		self.key ^= state ^ self.state_key();
+
+		undo
+	}
+
+	/* Unmake the given move, which must be the last move made on the
+	 * board, using the record returned by Board::make_move */
+	pub fn unmake_move(self: & mut Self, m: & Move, undo: & Undo)
+	{
+		/* The piece stands on the destination square, and goes back to
+		 * being a pawn if it was promoted */
+		let mut p = match *self.at(m.to)
+		{
+			Square::Occupied(p) => p,
+			Square::Empty => panic!("the move was not made on the board"),
+		};
+
+		if m.is_promotion().is_some()
+		{
+			p.kind = & PAWN;
+		}
+
+		self.set(m.to, Square::Empty);
+
+		/* Remove the rook before placing the king, in case the king
+		 * started on the rook's destination square */
+		if let (Some(rook), true) = (m.rook(), m.is_castle())
+		{
+			let r = *self.at(Self::castle_rook_to(m, rook));
+
+			self.set(Self::castle_rook_to(m, rook), Square::Empty);
+			self.set(rook, r);
+		}
+
+		self.set(m.from, Square::Occupied(p));
+
+		if m.is_passant()
+		{
+			self.set(Self::passant_capture_loc(m), undo.captured);
+		}
+		else if !m.is_castle()
+		{
+			self.set(m.to, undo.captured);
+		}
+
+		self.player = p.player;
+		self.passant = undo.passant;
+		self.castling = undo.castling;
+		self.halfmove_clock = undo.halfmove_clock;
+		self.fullmove = undo.fullmove;
+		self.key = undo.key;
+	}
+
+	/* Return the board that results from making the given move */
+	pub fn after_move(self: & Self, m: & Move) -> Self
 	{
 		let mut b = *self;
 
-		/* Disregard castling moves as they cannot capture anything */
-		/* This is necessary to prevent infinite recursion when
-		 * check castling availability */
-		b.castling =  [ Castling { k: None, q: None }; 2 ];
+		b.make_move(m);
+
+		b
+	}
+
+	/* Return the passant square only if the player who has the turn can
+	 * actually make a legal passant capture on it. */
+	pub fn passant_capturable(self: & Self) -> Option<Loc>
+	{
+		self.passant?;
+
+		if self.moves(self.player).iter().any(|m| m.is_passant())
+		{
+			self.passant
+		}
+		else
+		{
+			None
+		}
+	}
+
+	/* Check whether two boards represent the same position for the purpose
+	 * of the repetition rules: the same pieces on the same squares, the
+	 * same player to move, the same castling rights, and the same
+	 * possible passant capture. */
+	pub fn is_same_position(self: & Self, other: & Self) -> bool
+	{
+		self.player == other.player
+			&& self.squares == other.squares
+			&& self.castling == other.castling
+			&& self.passant_capturable()
+				== other.passant_capturable()
+	}
+
+	/* Check whether the given player can not possibly checkmate the
+	 * opponent by any sequence of legal moves. */
+	pub fn is_insufficient_material(self: & Self, player: Player) -> bool
+	{
+		/* Non-king pieces of the player and the opponent, and the
+		 * colors of the squares the bishops on the board stand on */
+		let mut own = Vec::<Piece>::new();
+		let mut other = Vec::<Piece>::new();
+		let mut bishop_colors = Vec::<i32>::new();
 
-		for m in b.piece_moves(player.opponent())
+		for loc in self.locations()
 		{
-			if let Square::Occupied(p) = b.at(m.to)
+			if let Square::Occupied(p) = self.at(loc)
 			{
-				if p.is(player, & KING)
+				if p.is_kind(& KING)
+				{
+					continue;
+				}
+
+				if p.is_kind(& BISHOP)
 				{
-					return true;
+					bishop_colors.push((loc.x + loc.y) % 2);
+				}
+
+				if p.is_player(player)
+				{
+					own.push(*p);
+				}
+				else
+				{
+					other.push(*p);
 				}
 			}
 		}
 
-		false
+		/* A lone king can not checkmate */
+		if own.is_empty()
+		{
+			return true;
+		}
+
+		/* A king and knight can only checkmate if the opponent has
+		 * pieces that can block the escape of their own king */
+		if own.len() == 1 && own[0].is_kind(& KNIGHT) && other.is_empty()
+		{
+			return true;
+		}
+
+		/* When there are no pieces other than bishops that all travel
+		 * on the same color, neither king can ever be checkmated */
+		if own.iter().chain(other.iter()).all(|p| p.is_kind(& BISHOP))
+			&& bishop_colors.iter().all(|c| *c == bishop_colors[0])
+		{
+			return true;
+		}
+
+		self.is_dead_pawn_structure()
 	}
 
-	/* Enumerate all legal moves */
-	pub fn moves(self: & Self, player: Player) -> Vec<Move>
+	/* Conservatively check whether the position is dead because of a
+	 * blocked pawn structure. This is the case when there are only kings
+	 * and pawns, no pawn can move or capture, and neither king can ever
+	 * reach an enemy pawn. No piece can then ever be captured, and no
+	 * king can ever be put in check. */
+	fn is_dead_pawn_structure(self: & Self) -> bool
 	{
-		let mut ms = self.piece_moves(player);
+		if self.passant.is_some()
+		{
+			return false;
+		}
 
-		/* Keep only legal moves, i.e. moves that do not put the moving
-		 * player's king in check. */
-		ms.retain(|m| !m.board.is_check(player));
+		for loc in self.locations()
+		{
+			let p = match self.at(loc)
+			{
+				Square::Occupied(p) if p.is_kind(& KING) => continue,
+				Square::Occupied(p) if p.is_kind(& PAWN) => p,
+				Square::Empty => continue,
+				Square::Occupied(_) => return false,
+			};
 
-		ms
+			let dir = match p.player
+			{
+				Player::White =>  1,
+				Player::Black => -1,
+			};
+
+			/* The pawn must be blocked by another pawn */
+			let step_loc = loc.offset((0, dir));
+			if !step_loc.valid() || !matches!(self.at(step_loc),
+					Square::Occupied(q) if q.is_kind(& PAWN))
+			{
+				return false;
+			}
+
+			/* The pawn must not have anything to capture */
+			for x in [-1, 1]
+			{
+				let to = loc.offset((x, dir));
+
+				if to.valid() && matches!(self.at(to),
+						Square::Occupied(q)
+						if !q.is_player(p.player))
+				{
+					return false;
+				}
+			}
+		}
+
+		/* Find every square that each king can reach, without
+		 * crossing its own pawns or squares attacked by enemy pawns,
+		 * and make sure that there are no enemy pawns among them. */
+		for player in [Player::White, Player::Black]
+		{
+			let back = match player
+			{
+				Player::White =>  1,
+				Player::Black => -1,
+			};
+
+			let attacked = |loc: Loc| [-1, 1].iter().any(|x|
+			{
+				let from = loc.offset((*x, back));
+
+				from.valid() && matches!(self.at(from),
+					Square::Occupied(q)
+					if q.is(player.opponent(), & PAWN))
+			});
+
+			let mut seen = [false; 8 * 8];
+			let mut todo = Vec::<Loc>::new();
+
+			for loc in self.locations()
+			{
+				if matches!(self.at(loc), Square::Occupied(q)
+						if q.is(player, & KING))
+				{
+					todo.push(loc);
+				}
+			}
+
+			while let Some(loc) = todo.pop()
+			{
+				let i = (loc.y * 8 + loc.x) as usize;
+
+				if seen[i]
+				{
+					continue;
+				}
+
+				seen[i] = true;
+
+				match self.at(loc)
+				{
+					Square::Occupied(q) if q.is(player, & PAWN)
+						=> continue,
+					Square::Occupied(q) if q.is_kind(& PAWN)
+						=> return false,
+					_ => (),
+				}
+
+				for dir in
+					[
+						(-1,  1), ( 0,  1), ( 1,  1),
+						(-1,  0),           ( 1,  0),
+						(-1, -1), ( 0, -1), ( 1, -1),
+					]
+				{
+					let to = loc.offset(dir);
+
+					if to.valid() && !attacked(to)
+					{
+						todo.push(to);
+					}
+				}
+			}
+		}
+
+		true
 	}
 
-	pub fn default() -> Self
+	/* Create a board without any pieces or castling rights, with white to
+	 * move */
+	pub fn empty() -> Self
 	{
-		/* Start with an empty board */
-		let mut b = Self
+		Self
 		{
 			player		: Player::White,
 			squares		: [Square::Empty; 8 * 8],
+			pieces		: [0; 6],
+			colors		: [0; 2],
+			key		: 0,
 			passant		: None,
-			castling	: Castling::DEFAULT,
-		};
+			castling	: [Castling { k: None, q: None }; 2],
+			halfmove_clock	: 0,
+			fullmove	: 1,
+		}
+	}
 
+	pub fn default() -> Self
+	{
 		/* Piece layout of the 1st and 8th rank */
-		let k =
+		Self::from_back_rank(
 		[
 			& ROOK,
 			& KNIGHT,
@@ -277,51 +959,115 @@ impl Board
 			& BISHOP,
 			& KNIGHT,
 			& ROOK,
-		];
+		])
+	}
+
+	/* Create the Chess960 starting position with the given number, from 0
+	 * to 959, as numbered by Reinhard Scharnagl. Number 518 is the
+	 * starting position of standard chess. */
+	pub fn chess960(n: u32) -> Option<Self>
+	{
+		if n >= 960
+		{
+			return None;
+		}
+
+		let mut k: [Option<& 'static PieceKind>; 8] = [None; 8];
+
+		/* The bishops go on opposite colors, the light-squared one on the
+		 * b, d, f or h file and the dark-squared one on the a, c, e or g
+		 * file */
+		k[(n % 4 * 2 + 1) as usize] = Some(& BISHOP);
+		k[(n / 4 % 4 * 2) as usize] = Some(& BISHOP);
+
+		/* Place a piece on the i-th file that is still empty */
+		let mut place = |kind, i|
+		{
+			let x = (0..8).filter(|x| k[*x].is_none()).nth(i).unwrap();
+
+			k[x] = Some(kind);
+		};
+
+		place(& QUEEN, (n / 16 % 6) as usize);
+
+		/* The knights take two of the five empty files, then the king
+		 * goes between the rooks on the last three */
+		let knights = [(0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3),
+			(1, 4), (2, 3), (2, 4), (3, 4)][(n / 96) as usize];
+
+		place(& KNIGHT, knights.1);
+		place(& KNIGHT, knights.0);
+		place(& ROOK, 0);
+		place(& KING, 0);
+		place(& ROOK, 0);
+
+		Some(Self::from_back_rank(k.map(|kind| kind.unwrap())))
+	}
+
+	/* Create a starting position with the given layout of the 1st and 8th
+	 * rank, and castling rights for both rooks, which must be on either
+	 * side of the king */
+	fn from_back_rank(k: [& 'static PieceKind; 8]) -> Self
+	{
+		/* Start with an empty board */
+		let mut b = Self::empty();
+
+		let rooks = (0..8)
+			.filter(|x| std::ptr::eq(k[*x as usize], & ROOK))
+			.collect::<Vec<i32>>();
+
+		for (player, y) in [(Player::White, 0), (Player::Black, 7)]
+		{
+			b.set_castling(player, Castling
+			{
+				k: Some(Loc { x: rooks[1], y }),
+				q: Some(Loc { x: rooks[0], y }),
+			});
+		}
 
 		for x in 0..8
 		{
 			/* White pieces */
-			*b.at_mut(Loc {x, y: 0}) = Square::Occupied
+			b.set(Loc {x, y: 0}, Square::Occupied
 			(
 				Piece
 				{
 					player	: Player::White,
 					kind	: k[x as usize],
 				},
-			);
-			*b.at_mut(Loc {x, y: 1}) = Square::Occupied
+			));
+			b.set(Loc {x, y: 1}, Square::Occupied
 			(
 				Piece
 				{
 					player	: Player::White,
 					kind	: & PAWN,
 				},
-			);
+			));
 
 			/* Black pieces */
-			*b.at_mut(Loc {x, y: 7}) = Square::Occupied
+			b.set(Loc {x, y: 7}, Square::Occupied
 			(
 				Piece
 				{
 					player	: Player::Black,
 					kind	: k[x as usize],
 				},
-			);
-			*b.at_mut(Loc {x, y: 6}) = Square::Occupied
+			));
+			b.set(Loc {x, y: 6}, Square::Occupied
 			(
 				Piece
 				{
 					player	: Player::Black,
 					kind	: & PAWN,
 				},
-			);
+			));
 		}
 
 		b
 	}
 
-	pub fn from_fen(fen: & str) -> Option<Self>
+	pub fn from_fen(fen: & str) -> Result<Self, FenError>
 	{
 		parse_fen(fen)
 	}
@@ -330,4 +1076,53 @@ impl Board
 	{
 		make_fen(self)
 	}
+
+	/* Count the positions reached after the given number of halfmoves */
+	pub fn perft(self: & Self, depth: u32) -> u64
+	{
+		perft(self, depth)
+	}
+
+	/* Count the positions reached after the given number of halfmoves by
+	 * the kind of the last move, and the checks and checkmates among them */
+	pub fn perft_stats(self: & Self, depth: u32) -> PerftStats
+	{
+		perft_stats(self, depth)
+	}
+
+	/* Count the positions reached after each of the legal moves */
+	pub fn perft_divide(self: & Self, depth: u32) -> Vec<(Move, PerftStats)>
+	{
+		perft_divide(self, depth)
+	}
+
+	/* Count the positions reached after the given number of halfmoves,
+	 * dividing the work between threads and caching the counts of
+	 * subtrees in the table, if one is given */
+	pub fn parallel_perft(self: & Self, depth: u32, threads: usize,
+			table: Option<& PerftTable>) -> u64
+	{
+		parallel_perft(self, depth, threads, table)
+	}
+
+	/* Check whether the position can be reached in a game, and report
+	 * every rule violation if it can not */
+	pub fn validate(self: & Self) -> Result<(), Vec<PositionError>>
+	{
+		validate_board(self)
+	}
+
+	/* Find the legal move for the player who has the turn that is written
+	 * as the given move in standard algebraic notation */
+	pub fn parse_san(self: & Self, san: & str) -> Result<Move, SanError>
+	{
+		parse_san(self, san)
+	}
+
+	/* Find the legal move for the player who has the turn that is written
+	 * as the given move in UCI notation */
+	pub fn parse_uci(self: & Self, uci: & str) -> Result<Move, UciError>
+	{
+		parse_uci(self, uci)
+	}
 }
@@ -0,0 +1,274 @@
+use crate::board::*;
+use crate::fen::*;
+use crate::move_notation::*;
+use crate::r#move::*;
+
+/* An operation of an EPD record, such as `bm Nf3 e4;` or `id "WAC.001";`.
+ * Quoted operands are stored without their quotes. */
+#[derive(Clone, PartialEq, Debug)]
+pub struct EpdOperation
+{
+	pub opcode	: String,
+	pub operands	: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+pub enum EpdError
+{
+	/* One of the four position fields is not valid FEN */
+	Position(FenError),
+	/* An operation is not valid, at the given character offset */
+	Operation
+	{
+		offset	: usize,
+		reason	: String,
+	},
+}
+
+impl std::fmt::Display for EpdError
+{
+	fn fmt(self: & Self, f: & mut std::fmt::Formatter) -> std::fmt::Result
+	{
+		match self
+		{
+			Self::Position(e) => write!(f, "{}", e),
+			Self::Operation { offset, reason } => write!(f,
+				"operation at character {}: {}", offset, reason),
+		}
+	}
+}
+
+impl std::error::Error for EpdError {}
+
+fn operation_error(offset: usize, reason: & str) -> EpdError
+{
+	EpdError::Operation { offset, reason: String::from(reason) }
+}
+
+/* A token of the operations, along with its character offset, and whether
+ * it was quoted */
+type Token = (String, usize, bool);
+
+fn make_operation(tokens: & [Token])
+	-> Result<(EpdOperation, usize), EpdError>
+{
+	let (opcode, offset, quoted) = & tokens[0];
+
+	let valid = !quoted
+		&& opcode.starts_with(|c: char| c.is_ascii_alphabetic())
+		&& opcode.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+	if !valid
+	{
+		return Err(operation_error(*offset, "invalid opcode"));
+	}
+
+	Ok((EpdOperation
+	{
+		opcode		: opcode.clone(),
+		operands	: tokens[1..].iter().map(|t| t.0.clone()).collect(),
+	}, *offset))
+}
+
+/* Parse the operations that follow the position fields, starting at the
+ * given character offset, along with the offset of each. Operations end
+ * with a semicolon, which may be left out at the end of the record, and
+ * empty operations are skipped. */
+fn parse_operations(s: & str, start: usize)
+	-> Result<Vec<(EpdOperation, usize)>, EpdError>
+{
+	let mut operations = Vec::<(EpdOperation, usize)>::new();
+	let mut tokens = Vec::<Token>::new();
+	let mut chars = s.chars().enumerate().map(|(i, c)| (i + start, c))
+		.peekable();
+
+	while let Some((o, c)) = chars.next()
+	{
+		match c
+		{
+			' ' | '\t' => (),
+			';' =>
+			{
+				if !tokens.is_empty()
+				{
+					operations.push(make_operation(& tokens)?);
+					tokens.clear();
+				}
+			},
+			'"' =>
+			{
+				let mut t = String::new();
+
+				loop
+				{
+					match chars.next()
+					{
+						Some((_, '"')) => break,
+						Some((_, c)) => t.push(c),
+						None => return Err(operation_error(o,
+							"unterminated string")),
+					}
+				}
+
+				tokens.push((t, o, true));
+			},
+			_ =>
+			{
+				let mut t = String::from(c);
+
+				while let Some((_, c)) = chars.next_if(|(_, c)|
+					!matches!(c, ' ' | '\t' | ';' | '"'))
+				{
+					t.push(c);
+				}
+
+				tokens.push((t, o, false));
+			},
+		}
+	}
+
+	if !tokens.is_empty()
+	{
+		operations.push(make_operation(& tokens)?);
+	}
+
+	Ok(operations)
+}
+
+/* A position in Extended Position Description, with its operations */
+#[derive(Clone)]
+pub struct Epd
+{
+	pub board	: Board,
+	pub operations	: Vec<EpdOperation>,
+}
+
+/* Parse an EPD record: the first four fields of a FEN string, followed by
+ * operations. The halfmove clock and fullmove number are taken from the
+ * hmvc and fmvn operations, or from two numbers that directly follow the
+ * fields, as some files have them. */
+pub fn parse_epd(epd: & str) -> Result<Epd, EpdError>
+{
+	let epd = epd.trim_end();
+
+	/* Find the fourth field, and any clocks after it */
+	let words = epd.split(' ').collect::<Vec<& str>>();
+	let mut fields = words.len().min(4);
+
+	while fields < words.len().min(6) && !words[fields].is_empty()
+		&& words[fields].chars().all(|c| c.is_ascii_digit())
+	{
+		fields += 1;
+	}
+
+	let end = words[..fields].iter().map(|w| w.len()).sum::<usize>()
+		+ fields - 1;
+
+	/* Let the FEN parser report missing fields */
+	let fen = match fields
+	{
+		4 => format!("{} 0 1", & epd[..end]),
+		5 => format!("{} 1", & epd[..end]),
+		_ => String::from(& epd[..end]),
+	};
+
+	let mut board = parse_fen(& fen).map_err(EpdError::Position)?;
+
+	let start = epd[..end].chars().count();
+	let operations = parse_operations(& epd[end..], start)?;
+
+	for (op, offset) in & operations
+	{
+		let (min, value) = match op.opcode.as_str()
+		{
+			"hmvc" => (0, & mut board.halfmove_clock),
+			"fmvn" => (1, & mut board.fullmove),
+			_ => continue,
+		};
+
+		*value = match op.operands[..]
+		{
+			[ref n] => n.parse::<i32>().ok().filter(|n| *n >= min),
+			_ => None,
+		}
+		.ok_or(operation_error(*offset, "invalid move number"))?;
+	}
+
+	Ok(Epd
+	{
+		board,
+		operations: operations.into_iter().map(|(op, _)| op).collect(),
+	})
+}
+
+impl Epd
+{
+	pub fn parse(epd: & str) -> Result<Self, EpdError>
+	{
+		parse_epd(epd)
+	}
+
+	/* The first operation with the given opcode */
+	pub fn operation(self: & Self, opcode: & str) -> Option<& EpdOperation>
+	{
+		self.operations.iter().find(|op| op.opcode == opcode)
+	}
+
+	pub fn id(self: & Self) -> Option<& str>
+	{
+		self.operation("id")
+			.and_then(|op| op.operands.first())
+			.map(|s| s.as_str())
+	}
+
+	/* The moves of the operands of the given opcode, in standard algebraic
+	 * notation */
+	fn san_moves(self: & Self, opcode: & str) -> Result<Vec<Move>, SanError>
+	{
+		self.operation(opcode).map_or(Ok(Vec::new()), |op| op.operands
+			.iter()
+			.map(|san| parse_san(& self.board, san))
+			.collect())
+	}
+
+	/* The moves of the bm operation */
+	pub fn best_moves(self: & Self) -> Result<Vec<Move>, SanError>
+	{
+		self.san_moves("bm")
+	}
+
+	/* The moves of the am operation */
+	pub fn avoid_moves(self: & Self) -> Result<Vec<Move>, SanError>
+	{
+		self.san_moves("am")
+	}
+
+	/* The expected perft counts of the D1, D2, ... operations, by depth */
+	pub fn perft_counts(self: & Self) -> Vec<(u32, u64)>
+	{
+		let mut counts = self.operations.iter()
+			.filter_map(|op| match (op.opcode.strip_prefix('D'),
+					& op.operands[..])
+			{
+				(Some(depth), [nodes]) => Some((depth.parse().ok()?,
+					nodes.parse().ok()?)),
+				_ => None,
+			})
+			.collect::<Vec<(u32, u64)>>();
+
+		counts.sort();
+		counts
+	}
+
+	/* Check whether the move is one of the best moves, if there are any,
+	 * and none of the moves to avoid */
+	pub fn solved_by(self: & Self, m: & Move) -> Result<bool, SanError>
+	{
+		let is = |ms: Vec<Move>| ms.iter().any(|n| n.pack() == m.pack());
+
+		let best = self.best_moves()?;
+		let avoid = self.avoid_moves()?;
+
+		Ok((best.is_empty() || is(best)) && !is(avoid))
+	}
+}
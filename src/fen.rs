@@ -1,3 +1,4 @@
+use crate::bitboard::*;
 use crate::board::*;
 use crate::piece::*;
 use crate::player::*;
@@ -60,179 +61,313 @@ fn parse_piece(c: char) -> Option<Vec<Square>>
 	}
 }
 
-fn parse_rank(ci: & mut std::iter::Peekable<std::str::Chars>)
-	-> Option<Vec<Square>>
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FenField
 {
-	let mut r = Vec::<Square>::new();
+	Placement,
+	Player,
+	Castling,
+	Passant,
+	HalfmoveClock,
+	FullmoveNumber,
+}
 
-	while r.len() < 8
+impl std::fmt::Display for FenField
+{
+	fn fmt(self: & Self, f: & mut std::fmt::Formatter) -> std::fmt::Result
 	{
-		match ci.peek()
+		match self
 		{
-			Some(c) =>
-			{
-				r.extend(parse_piece(*c)?);
-				ci.next();
-			},
-			_ => { break; },
+			Self::Placement		=> write!(f, "piece placement"),
+			Self::Player		=> write!(f, "side to move"),
+			Self::Castling		=> write!(f, "castling"),
+			Self::Passant		=> write!(f, "en passant"),
+			Self::HalfmoveClock	=> write!(f, "halfmove clock"),
+			Self::FullmoveNumber	=> write!(f, "fullmove number"),
 		}
 	}
+}
 
-	if r.len() == 8
-	{
-		Some(r)
-	}
-	else
+#[derive(Clone, Debug)]
+pub struct FenError
+{
+	pub field	: FenField,
+	pub offset	: usize,
+	pub reason	: String,
+}
+
+impl std::fmt::Display for FenError
+{
+	fn fmt(self: & Self, f: & mut std::fmt::Formatter) -> std::fmt::Result
 	{
-		None
+		write!(f, "{} field at character {}: {}",
+			self.field, self.offset, self.reason)
 	}
 }
 
-fn parse_squares(ci: & mut std::iter::Peekable<std::str::Chars>)
-	-> Option<Vec<Square>>
+impl std::error::Error for FenError {}
+
+fn fen_error(field: FenField, offset: usize, reason: String) -> FenError
 {
-	let mut s = Vec::<Square>::new();
+	FenError { field, offset, reason }
+}
+
+/* A field of a FEN string, along with its character offset */
+type Field<'a> = (& 'a str, usize);
 
-	while s.len() < 8 * 8
+fn parse_squares(f: Field) -> Result<[Square; 8 * 8], FenError>
+{
+	let err = |o, r| fen_error(FenField::Placement, f.1 + o, r);
+
+	let mut squares = [Square::Empty; 8 * 8];
+	let mut o = 0;
+
+	for (i, rank) in f.0.split('/').enumerate()
 	{
-		let mut r = parse_rank(ci)?;
-		r.append(& mut s);
-		s = r;
+		/* Ranks are listed from the 8th rank to the 1st */
+		if i >= 8
+		{
+			return Err(err(o, String::from("more than 8 ranks")));
+		}
+
+		let y = 7 - i as i32;
+		let mut x = 0;
+		let start = o;
 
-		match ci.next()
+		for c in rank.chars()
 		{
-			Some('/') => continue,
-			Some(' ') => break,
-			_ => return None,
+			let r = parse_piece(c).ok_or_else(|| err(o,
+				format!("invalid piece `{}`", c)))?;
+
+			for sq in r
+			{
+				if x < 8
+				{
+					squares[(y * 8 + x) as usize] = sq;
+				}
+
+				x += 1;
+			}
+
+			o += 1;
 		}
-	}
 
-	if s.len() == 8 * 8
-	{
-		Some(s)
+		if x != 8
+		{
+			return Err(err(start, format!("rank {} has {} files",
+						y + 1, x)));
+		}
+
+		/* Skip the slash */
+		o += 1;
+
+		if i == 7 && o > f.0.chars().count()
+		{
+			return Ok(squares);
+		}
 	}
-	else
+
+	Err(err(f.0.chars().count(), String::from("fewer than 8 ranks")))
+}
+
+fn parse_player(f: Field) -> Result<Player, FenError>
+{
+	match f.0
 	{
-		None
+		"w" => Ok(Player::White),
+		"b" => Ok(Player::Black),
+		_ => Err(fen_error(FenField::Player, f.1,
+			format!("expected `w` or `b`, found `{}`", f.0))),
 	}
 }
 
-fn parse_player(ci: & mut std::iter::Peekable<std::str::Chars>)
-	-> Option<Player>
+/* Check whether the location holds a rook of the given player */
+fn is_rook(b: & Board, player: Player, loc: Loc) -> bool
 {
-	let mut p = Player::White;
+	matches!(b.at(loc), Square::Occupied(p) if p.is(player, & ROOK))
+}
 
-	if matches!(ci.peek(), Some(' ')) { return None; }
+/* Parse the castling rights, either as KQkq or as the files of the rooks.
+ * As in X-FEN, K and Q refer to the outermost rook on that side of the
+ * king, and fall back to the corner if there is none. As in Shredder-FEN, a
+ * file refers to the rook on that file, castling towards the side of the
+ * king that it is on. */
+fn parse_castling(f: Field, b: & Board) -> Result<[Castling; 2], FenError>
+{
+	let mut c = [Castling { k: None, q: None }; 2];
 
-	loop
+	if f.0 == "-"
 	{
-		match ci.next()
+		return Ok(c);
+	}
+
+	for (o, ch) in f.0.chars().enumerate()
+	{
+		let player = match ch.is_ascii_uppercase()
+		{
+			true => Player::White,
+			false => Player::Black,
+		};
+
+		let y = match player
+		{
+			Player::White => 0,
+			Player::Black => 7,
+		};
+
+		let king = Bits(b.bitboard(player, & KING))
+			.find(|l| l.y == y)
+			.map_or(4, |l| l.x);
+
+		let (dir, rook) = match ch.to_ascii_lowercase()
+		{
+			'k' => (1, (king + 1..8).rev()
+				.map(|x| Loc { x, y })
+				.find(|l| is_rook(b, player, *l))
+				.unwrap_or(Loc { x: 7, y })),
+			'q' => (-1, (0..king)
+				.map(|x| Loc { x, y })
+				.find(|l| is_rook(b, player, *l))
+				.unwrap_or(Loc { x: 0, y })),
+			f @ 'a'..='h' =>
+			{
+				let x = f as i32 - 'a' as i32;
+
+				(if x > king { 1 } else { -1 }, Loc { x, y })
+			},
+			_ => return Err(fen_error(FenField::Castling, f.1 + o,
+				format!("invalid castling right `{}`", ch))),
+		};
+
+		let side = match dir
 		{
-			Some('w') => p = Player::White,
-			Some('b') => p = Player::Black,
-			Some(' ') => return Some(p),
-			_ => return None,
+			1 => & mut c[player as usize].k,
+			_ => & mut c[player as usize].q,
+		};
+
+		if side.is_some()
+		{
+			return Err(fen_error(FenField::Castling, f.1 + o,
+				format!("duplicate {} in castling field", ch)));
 		}
+
+		*side = Some(rook);
 	}
+
+	Ok(c)
 }
 
-fn parse_castling(ci: & mut std::iter::Peekable<std::str::Chars>)
-	-> Option<[Castling; 2]>
+fn parse_passant(f: Field) -> Result<Option<Loc>, FenError>
 {
-	let mut c = [Castling { k: None, q: None }; 2];
-
-	if matches!(ci.peek(), Some(' ')) { return None; }
-
-	if matches!(ci.peek(), Some('-')) { return Some(c); }
+	if f.0 == "-"
+	{
+		return Ok(None);
+	}
 
-	loop
+	match Loc::parse(f.0)
 	{
-		match ci.next()
-		{
-			Some('K') =>
-				if let Some(_) = c[0].k { return None }
-				else { c[0].k = Castling::DEFAULT[0].k },
-			Some('Q') =>
-				if let Some(_) = c[0].q { return None }
-				else { c[0].q = Castling::DEFAULT[0].q },
-			Some('k') =>
-				if let Some(_) = c[1].k { return None }
-				else { c[1].k = Castling::DEFAULT[1].k },
-			Some('q') =>
-				if let Some(_) = c[1].q { return None }
-				else { c[1].q = Castling::DEFAULT[1].q },
-			Some(' ') => return Some(c),
-			_ => return None,
-		}
+		Some(loc) => Ok(Some(loc)),
+		None => Err(fen_error(FenField::Passant, f.1,
+			format!("invalid square `{}`", f.0))),
 	}
 }
 
-fn parse_passant(ci: & mut std::iter::Peekable<std::str::Chars>)
-	-> Option<Option<Loc>>
+fn parse_number(f: Field, field: FenField, min: i32) -> Result<i32, FenError>
 {
-	if matches!(ci.peek(), Some('-'))
+	match f.0.parse::<i32>()
 	{
-		ci.next();
+		Ok(n) if n >= min && f.0.chars().all(|c| c.is_ascii_digit())
+			=> Ok(n),
+		_ => Err(fen_error(field, f.1,
+			format!("invalid {} `{}`", field, f.0))),
+	}
+}
 
-		if matches!(ci.peek(), Some(' '))
-		{
-			ci.next();
+pub fn parse_fen(fen: & str) -> Result<Board, FenError>
+{
+	/* Split the FEN string into its fields, keeping track of the character
+	 * offset of each field. Any run of whitespace separates two fields,
+	 * and whitespace at either end is ignored. */
+	let mut fields = Vec::<Field>::new();
+	let mut start = None;
 
-			Some(None)
-		}
-		else
+	for (o, (i, c)) in fen.char_indices().enumerate()
+	{
+		match start
 		{
-			None
+			Some((s, so)) if c.is_whitespace() =>
+			{
+				fields.push((& fen[s..i], so));
+				start = None;
+			},
+			None if !c.is_whitespace() => start = Some((i, o)),
+			_ => (),
 		}
 	}
-	else
+
+	if let Some((s, so)) = start
 	{
-		let mut s = String::new();
-		s.push(ci.next()?);
-		s.push(ci.next()?);
+		fields.push((& fen[s..], so));
+	}
 
-		let p = Loc::parse(s.as_str())?;
+	/* The character offset right after a field */
+	let end = |f: & Field| f.1 + f.0.chars().count();
+
+	let names =
+	[
+		FenField::Placement,
+		FenField::Player,
+		FenField::Castling,
+		FenField::Passant,
+		FenField::HalfmoveClock,
+		FenField::FullmoveNumber,
+	];
+
+	/* Report missing fields at the end of the last field */
+	if fields.len() < names.len()
+	{
+		let field = names[fields.len()];
+		let o = fields.last().map(end).unwrap_or(0);
 
-		if matches!(ci.peek(), Some(' '))
-		{
-			ci.next();
+		return Err(fen_error(field, o, format!("missing {}", field)));
+	}
 
-			Some(Some(p))
-		}
-		else
-		{
-			None
-		}
+	if fields.len() > names.len()
+	{
+		return Err(fen_error(FenField::FullmoveNumber, end(& fields[5]),
+			String::from("unexpected text after fullmove number")));
 	}
-}
 
-pub fn parse_fen(fen: & str) -> Option<Board>
-{
-	let mut ci = fen.chars().peekable();
+	let squares = parse_squares(fields[0])?;
 
-	let squares_v = parse_squares(& mut ci)?;
-	let mut squares = [Square::Empty; 8 * 8];
+	let player = parse_player(fields[1])?;
 
-	for i in 0..squares_v.len()
+	let mut board = Board::empty();
+
+	for (loc, sq) in board.locations().zip(squares)
 	{
-		squares[i] = squares_v[i];
+		board.set(loc, sq);
 	}
 
-	let player = parse_player(& mut ci)?;
+	/* The castling rights depend on where the kings and rooks are */
+	let castling = parse_castling(fields[2], & board)?;
 
-	let castling = parse_castling(& mut ci)?;
+	let passant = parse_passant(fields[3])?;
 
-	let passant = parse_passant(& mut ci)?;
+	let halfmove_clock = parse_number(fields[4],
+			FenField::HalfmoveClock, 0)?;
 
-	let board = Board
-	{
-		player,
-		squares,
-		passant,
-		castling,
-	};
+	let fullmove = parse_number(fields[5],
+			FenField::FullmoveNumber, 1)?;
 
-	Some(board)
+	board.set_player(player);
+	board.set_passant(passant);
+	board.set_castling(Player::White, castling[0]);
+	board.set_castling(Player::Black, castling[1]);
+	board.halfmove_clock = halfmove_clock;
+	board.fullmove = fullmove;
+
+	Ok(board)
 }
 
 fn put_piece(fen: & mut String, p: Option<& Piece>, ne: & mut i32)
@@ -258,6 +393,30 @@ fn put_piece(fen: & mut String, p: Option<& Piece>, ne: & mut i32)
 	}
 }
 
+/* Write a castling right as in X-FEN: K or Q for the outermost rook on its
+ * side of the king, which is always the case in standard chess, and the
+ * file of the rook otherwise */
+fn castling_char(b: & Board, player: Player, rook: Loc, dir: i32) -> char
+{
+	let outermost = !(1..8)
+		.map(|i| rook.offset((i * dir, 0)))
+		.take_while(|l| l.valid())
+		.any(|l| is_rook(b, player, l));
+
+	let c = match (outermost, dir)
+	{
+		(true, 1) => 'k',
+		(true, _) => 'q',
+		(false, _) => (b'a' + rook.x as u8) as char,
+	};
+
+	match player
+	{
+		Player::White => c.to_ascii_uppercase(),
+		Player::Black => c,
+	}
+}
+
 pub fn make_fen(board: & Board) -> String
 {
 	let mut fen = String::new();
@@ -285,23 +444,34 @@ pub fn make_fen(board: & Board) -> String
 		put_piece(& mut fen, None, & mut ne);
 	}
 
-	let player = match board.player
+	let player = match board.player()
 		{ Player::White => "w", Player::Black => "b" };
 
 	let mut castling = String::new();
-	if let Some(_) = board.castling(Player::White).k { castling.push('K') }
-	if let Some(_) = board.castling(Player::White).q { castling.push('Q') }
-	if let Some(_) = board.castling(Player::Black).k { castling.push('k') }
-	if let Some(_) = board.castling(Player::Black).q { castling.push('q') }
+
+	for player in [Player::White, Player::Black]
+	{
+		let c = board.castling(player);
+
+		for (rook, dir) in [(c.k, 1), (c.q, -1)]
+		{
+			if let Some(rook) = rook
+			{
+				castling.push(castling_char(board, player, rook, dir));
+			}
+		}
+	}
+
 	if castling.len() == 0 { castling.push('-') }
 
-	let passant = match board.passant
+	let passant = match board.passant()
 	{
 		Some(loc) => loc.notation(true, true),
 		None => String::from("-"),
 	};
 
-	let s = format!(" {} {} {} 0 1", player, castling, passant);
+	let s = format!(" {} {} {} {} {}", player, castling, passant,
+			board.halfmove_clock, board.fullmove);
 	fen.push_str(s.as_str());
 
 	fen
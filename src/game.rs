@@ -1,6 +1,10 @@
 use crate::board::*;
+use crate::move_notation::*;
+use crate::pgn::*;
 use crate::r#move::*;
 use crate::player::*;
+use crate::validate::*;
+use crate::zobrist::*;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum State
@@ -8,13 +12,61 @@ pub enum State
 	Playing,
 	Checkmate,
 	Stalemate,
+	ThreefoldRepetition,
+	FivefoldRepetition,
+	FiftyMoveRule,
+	SeventyFiveMoveRule,
+	InsufficientMaterial,
+	Resignation(Player),
+	DrawAgreement,
+	Adjudication(Option<Player>),
+}
+
+/* The annotation of a move. A closing brace in the comment is written as a
+ * closing parenthesis in PGN, since it would end the comment. */
+#[derive(Clone, Default)]
+pub struct Annotation
+{
+	pub comment	: Option<String>,
+	pub nags	: Vec<u8>,
+	pub clock	: Option<std::time::Duration>,
+}
+
+impl Annotation
+{
+	pub fn new() -> Self
+	{
+		Self::default()
+	}
+}
+
+/* The Zobrist hash of the board, with the passant file only included if a
+ * passant capture is legal, so that boards have the same hash exactly when
+ * Board::is_same_position holds. The Zobrist hash includes it whenever a
+ * pawn stands ready to capture, even if the pawn is pinned. */
+fn repetition_hash(b: & Board) -> u64
+{
+	match b.passant_capturable()
+	{
+		Some(_) => b.hash(),
+		None => b.hash() ^ passant_key(b),
+	}
 }
 
 #[derive(Clone)]
 pub struct Game
 {
 	start_board	: Board,
+	board		: Board,
+	setup		: bool,
 	moves		: Vec<Move>,
+	/* The repetition hash of the board after each halfmove, starting
+	 * with the start board */
+	hashes		: Vec<u64>,
+	annotations	: Vec<Annotation>,
+	tags		: Vec<(String, String)>,
+	result		: Option<State>,
+	draw_offer	: Option<(Player, i32)>,
 }
 
 impl Game
@@ -24,7 +76,14 @@ impl Game
 		Game
 		{
 			start_board	: Board::default(),
+			board		: Board::default(),
+			setup		: false,
 			moves		: Vec::<Move>::new(),
+			hashes		: vec![repetition_hash(& Board::default())],
+			annotations	: vec![Annotation::new()],
+			tags		: Vec::<(String, String)>::new(),
+			result		: None,
+			draw_offer	: None,
 		}
 	}
 
@@ -33,30 +92,48 @@ impl Game
 		Game
 		{
 			start_board	: board,
+			board,
+			setup		: true,
 			moves		: Vec::<Move>::new(),
+			hashes		: vec![repetition_hash(& board)],
+			annotations	: vec![Annotation::new()],
+			tags		: Vec::<(String, String)>::new(),
+			result		: None,
+			draw_offer	: None,
 		}
 	}
 
+	/* Create a game from the given board, unless the position could not
+	 * have been reached in a game */
+	pub fn try_from_board(board: Board) -> Result<Self, Vec<PositionError>>
+	{
+		board.validate()?;
+
+		Ok(Self::from_board(board))
+	}
+
 	pub fn player(self: & Self) -> Player
 	{
-		self.board().player
+		self.board().player()
 	}
 
 	pub fn board(self: & Self) -> Board
 	{
-		self.board_at(self.halfmove())
+		self.board
 	}
 
+	/* Return the board after the given number of halfmoves, found by
+	 * replaying the moves from the start of the game */
 	pub fn board_at(self: & Self, halfmove: i32) -> Board
 	{
-		if halfmove == 0
-		{
-			self.start_board
-		}
-		else
+		let mut b = self.start_board;
+
+		for m in & self.moves[..halfmove as usize]
 		{
-			self.moves[(halfmove - 1) as usize].board
+			b.make_move(m);
 		}
+
+		b
 	}
 
 	pub fn get_moves(self: & Self, from: Option<Loc>, to: Option<Loc>)
@@ -78,7 +155,69 @@ impl Game
 
 	pub fn play_move(self: & mut Self, r#move: & Move)
 	{
+		self.board.make_move(r#move);
 		self.moves.push(*r#move);
+		self.hashes.push(repetition_hash(& self.board));
+		self.annotations.push(Annotation::new());
+	}
+
+	/* Play the move given in standard algebraic notation */
+	pub fn play_san(self: & mut Self, san: & str) -> Result<Move, SanError>
+	{
+		let m = self.board().parse_san(san)?;
+
+		self.play_move(& m);
+
+		Ok(m)
+	}
+
+	/* Return the moves that have been played in the game */
+	pub fn played_moves(self: & Self) -> & Vec<Move>
+	{
+		& self.moves
+	}
+
+	/* Check whether the game was set up from a board with Game::from_board
+	 */
+	pub fn is_setup(self: & Self) -> bool
+	{
+		self.setup
+	}
+
+	/* Return the annotation of the given halfmove. The annotation of
+	 * halfmove 0 belongs to the game as a whole. */
+	pub fn annotation(self: & Self, halfmove: i32) -> & Annotation
+	{
+		& self.annotations[halfmove as usize]
+	}
+
+	pub fn annotation_mut(self: & mut Self, halfmove: i32)
+		-> & mut Annotation
+	{
+		& mut self.annotations[halfmove as usize]
+	}
+
+	pub fn tags(self: & Self) -> & Vec<(String, String)>
+	{
+		& self.tags
+	}
+
+	pub fn tag(self: & Self, name: & str) -> Option<& str>
+	{
+		self.tags.iter()
+			.find(|t| t.0 == name)
+			.map(|t| t.1.as_str())
+	}
+
+	/* Set the value of a tag, replacing any previous value */
+	pub fn set_tag(self: & mut Self, name: & str, value: & str)
+	{
+		match self.tags.iter_mut().find(|t| t.0 == name)
+		{
+			Some(t) => t.1 = String::from(value),
+			None => self.tags.push((String::from(name),
+						String::from(value))),
+		}
 	}
 
 	pub fn halfmove(self: & Self) -> i32
@@ -88,11 +227,187 @@ impl Game
 
 	pub fn fullmove(self: & Self) -> i32
 	{
-		1 + (self.moves.len() / 2) as i32
+		self.board().fullmove
+	}
+
+	/* Count the number of times the current position has occurred in the
+	 * game, including the current occurrence. Positions are compared by
+	 * their repetition hashes. */
+	pub fn repetitions(self: & Self) -> i32
+	{
+		let h = self.halfmove();
+		let hash = self.hashes[h as usize];
+
+		/* Positions before the last capture or pawn move can never be
+		 * the same, and neither can positions with the other player to
+		 * move, so only every other position since then needs to be
+		 * compared. */
+		let first = (h - self.board().halfmove_clock).max(0);
+
+		(first..=h)
+			.filter(|i| (h - i) % 2 == 0 && self.hashes[*i as usize] == hash)
+			.count() as i32
+	}
+
+	/* Return the player who had the turn after the given number of
+	 * halfmoves */
+	fn player_at(self: & Self, halfmove: i32) -> Player
+	{
+		match halfmove % 2
+		{
+			0 => self.start_board.player(),
+			_ => self.start_board.player().opponent(),
+		}
+	}
+
+	/* Return the state that a draw claim would end the game with, if the
+	 * player who has the turn may claim a draw. */
+	fn draw_claim(self: & Self) -> Option<State>
+	{
+		if self.state() != State::Playing
+		{
+			None
+		}
+		else if self.repetitions() >= 3
+		{
+			Some(State::ThreefoldRepetition)
+		}
+		else if self.board().halfmove_clock >= 100
+		{
+			Some(State::FiftyMoveRule)
+		}
+		else
+		{
+			None
+		}
+	}
+
+	/* Check whether the player who has the turn may claim a draw */
+	pub fn can_claim_draw(self: & Self) -> bool
+	{
+		self.draw_claim().is_some()
+	}
+
+	/* Claim a draw on behalf of the player who has the turn. Returns true
+	 * if the claim was valid, in which case the game has ended. */
+	pub fn claim_draw(self: & mut Self) -> bool
+	{
+		match self.draw_claim()
+		{
+			Some(state) => self.end(state),
+			None => false,
+		}
+	}
+
+	/* Resign the game on behalf of the given player. Returns true if the
+	 * game was in progress, in which case the game has ended. */
+	pub fn resign(self: & mut Self, player: Player) -> bool
+	{
+		self.end(State::Resignation(player))
+	}
+
+	/* Offer a draw on behalf of the given player. The offer is tied to the
+	 * current halfmove, and stands until the opponent has made a move or
+	 * declined it. Returns true if the game was in progress. */
+	pub fn offer_draw(self: & mut Self, player: Player) -> bool
+	{
+		if self.state() != State::Playing
+		{
+			return false;
+		}
+
+		self.draw_offer = Some((player, self.halfmove()));
+
+		true
+	}
+
+	/* Return the player who has made a draw offer that still stands, and
+	 * the halfmove it was made on. */
+	pub fn draw_offer(self: & Self) -> Option<(Player, i32)>
+	{
+		let (player, halfmove) = self.draw_offer?;
+
+		/* The offer lapses as soon as the opponent makes a move. If the
+		 * offering player had the turn, they get to make their move
+		 * first. */
+		let mut lapse = halfmove;
+
+		if self.player_at(halfmove) == player
+		{
+			lapse += 1;
+		}
+
+		if self.halfmove() <= lapse
+		{
+			self.draw_offer
+		}
+		else
+		{
+			None
+		}
+	}
+
+	/* Accept the standing draw offer on behalf of the given player, who
+	 * must be the opponent of the player who made the offer. Returns true
+	 * if the offer was accepted, in which case the game has ended. */
+	pub fn accept_draw(self: & mut Self, player: Player) -> bool
+	{
+		match self.draw_offer()
+		{
+			Some((p, _)) if p == player.opponent()
+				=> self.end(State::DrawAgreement),
+			_ => false,
+		}
+	}
+
+	/* Decline the standing draw offer on behalf of the given player, who
+	 * must be the opponent of the player who made the offer. Returns true
+	 * if there was such an offer. */
+	pub fn decline_draw(self: & mut Self, player: Player) -> bool
+	{
+		match self.draw_offer()
+		{
+			Some((p, _)) if p == player.opponent() =>
+			{
+				self.draw_offer = None;
+
+				true
+			},
+			_ => false,
+		}
+	}
+
+	/* Record the result of the game as decided by an arbiter, with the
+	 * given winner, or a draw if there is none. Returns true if the game
+	 * was in progress, in which case the game has ended. */
+	pub fn adjudicate(self: & mut Self, winner: Option<Player>) -> bool
+	{
+		self.end(State::Adjudication(winner))
+	}
+
+	/* End a game in progress with the given state */
+	fn end(self: & mut Self, state: State) -> bool
+	{
+		if self.state() != State::Playing
+		{
+			return false;
+		}
+
+		self.result = Some(state);
+		self.draw_offer = None;
+
+		true
 	}
 
 	pub fn state(self: & Self) -> State
 	{
+		/* A game that has been ended by a claim or agreement stays
+		 * ended */
+		if let Some(result) = self.result
+		{
+			return result;
+		}
+
 		if self.get_moves(None, None).len() == 0
 		{
 			if self.board().is_check(self.player())
@@ -104,6 +419,19 @@ impl Game
 				State::Stalemate
 			}
 		}
+		else if self.board().is_insufficient_material(Player::White)
+			&& self.board().is_insufficient_material(Player::Black)
+		{
+			State::InsufficientMaterial
+		}
+		else if self.repetitions() >= 5
+		{
+			State::FivefoldRepetition
+		}
+		else if self.board().halfmove_clock >= 150
+		{
+			State::SeventyFiveMoveRule
+		}
 		else
 		{
 			State::Playing
@@ -115,56 +443,46 @@ impl Game
 		match self.state()
 		{
 			State::Playing => [0, 0],
-			State::Stalemate => [1, 1],
+			State::Stalemate
+			| State::ThreefoldRepetition
+			| State::FivefoldRepetition
+			| State::FiftyMoveRule
+			| State::SeventyFiveMoveRule
+			| State::InsufficientMaterial
+			| State::DrawAgreement
+			| State::Adjudication(None) => [1, 1],
 			State::Checkmate => match self.player()
 			{
 				Player::White => [0, 2],
 				Player::Black => [2, 0],
-			}
+			},
+			State::Resignation(Player::White)
+			| State::Adjudication(Some(Player::Black)) => [0, 2],
+			State::Resignation(Player::Black)
+			| State::Adjudication(Some(Player::White)) => [2, 0],
 		}
 	}
 
 	pub fn movetext(self: & Self) -> String
 	{
-		let mut s = String::new();
-
-		for i in 0..self.halfmove()
-		{
-			let b = self.board_at(i);
-			let m = & self.moves[i as usize];
-			let ms = b.moves(b.player);
-
-			/* Print a space in between moves */
-			if i != 0
-			{
-				s.push(' ');
-			}
-
-			/* Print the move number */
-			if i % 2 == 0
-			{
-				s.push_str(format!("{}. ", 1 + i / 2)
-					.as_str());	
-			}
-
-			/* Print the move notation */
-			s.push_str(format!("{}", m.notation(& b, & ms))
-					.as_str());
-		}
+		let mut ts = make_movetext(self, false);
 
 		/* Print the score if the game has ended */
 		if self.state() != State::Playing
 		{
-			let r = self.score().map(|r| match r
-				{
-					1 => "1/2",
-					2 => "1",
-					_ => "0",
-				});
-
-			s.push_str(format!(" {}-{}", r[0], r[1]).as_str());
+			ts.push(result_token(self));
 		}
 
-		s
+		ts.join(" ")
+	}
+
+	pub fn to_pgn(self: & Self) -> String
+	{
+		make_pgn(self)
+	}
+
+	pub fn from_pgn(pgn: & str) -> Result<Self, PgnError>
+	{
+		parse_pgn(pgn)
 	}
 }
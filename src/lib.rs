@@ -1,14 +1,34 @@
+mod bitboard;
 mod board;
+mod epd;
 mod fen;
 mod game;
 mod r#move;
 mod move_notation;
+mod move_picker;
+mod packed_move;
+mod perft;
+mod pgn;
+mod polyglot;
 mod piece;
 mod player;
 mod tests;
+mod validate;
+mod zobrist;
 
-pub use crate::board::{Loc, Square, Castling, Board};
-pub use crate::game::{State, Game};
+pub use crate::bitboard::Bits;
+pub use crate::board::{Loc, Square, Castling, Undo, Board};
+pub use crate::epd::{EpdOperation, EpdError, Epd};
+pub use crate::fen::{FenField, FenError};
+pub use crate::game::{State, Annotation, Game};
 pub use crate::r#move::Move;
+pub use crate::move_notation::{SanError, UciError};
+pub use crate::move_picker::{MoveStage, MovePicker};
+pub use crate::packed_move::PackedMove;
+pub use crate::perft::{PerftTable, PerftStats};
+pub use crate::pgn::{PgnError, PgnSkip, PgnReader};
 pub use crate::piece::{PieceKind, Piece, KING, QUEEN, ROOK, BISHOP, KNIGHT, PAWN};
 pub use crate::player::Player;
+pub use crate::polyglot::{POLYGLOT_KEY_COUNT, POLYGLOT_RANDOM, PolyglotKeys,
+	BookEntry, PolyglotBook, BookBuilder};
+pub use crate::validate::PositionError;
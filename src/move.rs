@@ -1,7 +1,10 @@
+use crate::bitboard::*;
 use crate::board::*;
 use crate::piece::*;
 use crate::player::*;
 use crate::move_notation::*;
+use crate::packed_move::*;
+use crate::polyglot::*;
 
 #[derive(Copy, Clone)]
 pub struct MoveFlags
@@ -10,25 +13,48 @@ pub struct MoveFlags
 	pub passant	: bool,
 	pub castle	: bool,
 	pub promotion	: bool,
+	pub double_push	: bool,
 }
 
+/* A move only describes what changes on the board, the resulting board is
+ * found by making the move on the board with Board::make_move. The piece
+ * that moves is the one on the square of departure, and the rook of a
+ * castling move stands on the file rook_file of the king's rank. */
 #[derive(Copy, Clone)]
 pub struct Move
 {
-	pub board	: Board,
-	pub piece	: Piece,
 	pub from	: Loc,
 	pub to		: Loc,
-	pub notation_fn	: fn(r#move: & Move, board: & Board,
-				disambiguate: & Vec<Move>) -> String,
+	pub promotion	: Option<& 'static PieceKind>,
 	pub flags	: MoveFlags,
+	pub rook_file	: Option<u8>,
 }
 
 impl Move
 {
-	pub fn is_check(self: & Self) -> bool
+	/* Return the piece that makes the move on the given board, which the
+	 * move must have been generated for */
+	pub fn piece(self: & Self, board: & Board) -> Piece
 	{
-		self.board.is_check(self.board.player)
+		match board.at(self.from)
+		{
+			Square::Occupied(p) => *p,
+			Square::Empty => panic!("no piece to move on {}",
+				self.from.notation(true, true)),
+		}
+	}
+
+	/* Return the location of the rook of a castling move */
+	pub fn rook(self: & Self) -> Option<Loc>
+	{
+		self.rook_file.map(|x| Loc { x: x as i32, y: self.from.y })
+	}
+
+	/* Check whether the move, made on the given board, puts the opponent
+	 * in check */
+	pub fn is_check(self: & Self, board: & Board) -> bool
+	{
+		board.after_move(self).is_check(board.player().opponent())
 	}
 
 	pub fn is_castle(self: & Self) -> bool
@@ -48,89 +74,77 @@ impl Move
 
 	pub fn is_promotion(self: & Self) -> Option<& PieceKind>
 	{
-		if !self.flags.promotion
+		self.promotion
+	}
+
+	/* Return the move in standard algebraic notation, as made on the given
+	 * board, disambiguated from the given moves */
+	pub fn notation(self: & Self, board: & Board,
+			disambiguate: & Vec<Move>) -> String
+	{
+		if self.is_castle()
 		{
-			None
+			castle_move_notation(self, board)
 		}
-		else if let Square::Occupied(q) = self.board.at(self.to)
+		else if self.piece(board).is_kind(& PAWN)
 		{
-			if q.is_kind(self.piece.kind)
-			{
-				None
-			}
-			else
-			{
-				Some(q.kind)
-			}
+			pawn_move_notation(self, board)
 		}
 		else
 		{
-			None
+			default_move_notation(self, board, disambiguate)
 		}
 	}
 
-	pub fn notation(self: & Self, board: & Board,
-			disambiguate: & Vec<Move>) -> String
+	/* Return the move in the long algebraic notation used by UCI, such as
+	 * e2e4 or e7e8q. Castling moves are written as the king moving to its
+	 * destination square, unless the king stays on its square. */
+	pub fn uci(self: & Self) -> String
 	{
-		(self.notation_fn)(self, board, disambiguate)
+		uci_move_notation(self, false)
 	}
-}
 
-/* Default move (chess move that is) constructor. Create a single move where
- * the given piece on the given board moves from 'from' to 'to'. */
-pub fn single_move(b: & Board, p: & Piece, from: Loc, to: Loc) -> Move
-{
-	let mut mb = *b;
-
-	*mb.at_mut(from) = Square::Empty;
-	*mb.at_mut(to) = Square::Occupied(*p);
-
-	/* Pass the turn */
-	mb.player = mb.player.opponent();
+	/* Return the move in UCI notation, with castling moves written as the
+	 * king capturing its own rook, as is done in Chess960. */
+	pub fn uci_chess960(self: & Self) -> String
+	{
+		uci_move_notation(self, true)
+	}
 
-	/* Clear the passant square, moves that allow passant should set this
-	 * in the returned move. */
-	mb.passant = None;
+	/* Return the move packed into 16 bits */
+	pub fn pack(self: & Self) -> PackedMove
+	{
+		PackedMove::new(self)
+	}
 
-	/* Check for moves to or from castling squares, castling rights for
-	 * that square is removed. */
-	for loc in [from, to]
+	/* Return the move encoded as in Polyglot opening books */
+	pub fn polyglot(self: & Self) -> u16
 	{
-		if Some(loc) == mb.castling(Player::White).k
-		{
-			mb.castling_mut(Player::White).k = None;
-		}
-		if Some(loc) == mb.castling(Player::White).q
-		{
-			mb.castling_mut(Player::White).q = None;
-		}
-		if Some(loc) == mb.castling(Player::Black).k
-		{
-			mb.castling_mut(Player::Black).k = None;
-		}
-		if Some(loc) == mb.castling(Player::Black).q
-		{
-			mb.castling_mut(Player::Black).q = None;
-		}
+		encode_book_move(self)
 	}
+}
 
+/* Default move (chess move that is) constructor. Create a single move where
+ * the given piece on the given board moves from 'from' to 'to'. */
+pub fn single_move(b: & Board, p: & Piece, from: Loc, to: Loc) -> Move
+{
 	let capture = matches!(b.at(to), Square::Occupied(q)
 				if q.player != p.player);
 
 	Move
 	{
-		board		: mb,
-		piece		: *p,
 		from,
 		to,
-		notation_fn	: default_move_notation,
+		promotion	: None,
 		flags		: MoveFlags
 		{
 			capture,
 			passant		: false,
 			castle		: false,
 			promotion	: false,
+			double_push	: false,
 		},
+		rook_file	: None,
 	}
 }
 
@@ -150,11 +164,8 @@ pub fn single_to_promo(m: & Move) -> Vec<Move>
 	{
 		let mut n = *m;
 
-		if let Square::Occupied(p) = n.board.at_mut(n.to)
-		{
-			p.kind = k;
-			n.flags.promotion = true;
-		}
+		n.promotion = Some(k);
+		n.flags.promotion = true;
 
 		ms.push(n);
 	}
@@ -174,69 +185,44 @@ pub fn multi_to_promo(ms: & Vec<Move>) -> Vec<Move>
 	ns
 }
 
-pub fn directional_moves(b: & Board, p: & Piece, from: Loc, dir: (i32, i32))
+/* Create a single move to each of the attacked squares that is not
+ * occupied by the moving player's own pieces */
+pub fn attack_moves(b: & Board, p: & Piece, from: Loc, attacks: u64)
 	-> Vec<Move>
 {
-	let mut ms = Vec::<Move>::new();
-
-	let mut to = from.offset(dir);
-
-	while to.valid()
-	{
-		let m = single_move(b, p, from, to);
-
-		match b.at(to)
-		{
-			Square::Occupied(q) =>
-			{
-				if p.player == q.player.opponent()
-				{
-					ms.push(m);
-				}
-
-				break;
-			}
-			Square::Empty => ms.push(m),
-		}
-
-		to = to.offset(dir);
-	}
+	Bits(attacks & !b.occupancy(p.player))
+		.map(|to| single_move(b, p, from, to))
+		.collect()
+}
 
-	ms
+fn occupied(b: & Board) -> u64
+{
+	b.occupancy(Player::White) | b.occupancy(Player::Black)
 }
 
 pub fn diagonal_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
 {
-	let mut ms = Vec::<Move>::new();
-
-	for dir in [(-1,  1), (-1, -1), ( 1,  1), ( 1, -1)]
-	{
-		ms.extend(directional_moves(b, p, loc, dir));
-	}
-
-	ms
+	attack_moves(b, p, loc, diagonal_attacks(loc, occupied(b)))
 }
 
 pub fn cardinal_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
 {
-	let mut ms = Vec::<Move>::new();
-
-	for dir in [(-1,  0), ( 0,  1), ( 0, -1), ( 1,  0)]
-	{
-		ms.extend(directional_moves(b, p, loc, dir));
-	}
-
-	ms
+	attack_moves(b, p, loc, cardinal_attacks(loc, occupied(b)))
 }
 
 pub fn queen_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
 {
-	let mut ms = Vec::<Move>::new();
+	let occupied = occupied(b);
 
-	ms.extend(diagonal_moves(b, p, loc));
-	ms.extend(cardinal_moves(b, p, loc));
+	attack_moves(b, p, loc, diagonal_attacks(loc, occupied)
+				| cardinal_attacks(loc, occupied))
+}
 
-	ms
+/* The square the king ends up on when castling towards the given direction,
+ * on the g file for O-O and the c file for O-O-O, as in Chess960 */
+pub fn castle_king_to(loc: Loc, dir: i32) -> Loc
+{
+	Loc { x: if dir > 0 { 6 } else { 2 }, y: loc.y }
 }
 
 pub fn check_castle(b: & Board, p: & Piece, loc: Loc, rook_loc: Option<Loc>,
@@ -244,68 +230,56 @@ pub fn check_castle(b: & Board, p: & Piece, loc: Loc, rook_loc: Option<Loc>,
 	-> Option<Loc>
 {
 	/* Stop if the player doesn't have castling rights */
-	if let None = rook_loc
+	let rook = rook_loc?;
+
+	/* The rook must be on the castling square, on the king's side */
+	if rook.y != loc.y || (rook.x - loc.x) * dir <= 0
+		|| !matches!(b.at(rook), Square::Occupied(q)
+			if q.is(p.player, & ROOK))
 	{
 		return None;
 	}
 
-	{
-		let mut to = loc.offset((dir, 0));
-
-		/* Look for a rook connected to the king to castle with */
-		while to.valid()
-		{
-			if let Square::Occupied(q) = b.at(to)
-			{
-				if q.is(p.player, & ROOK)
-				{
-					break;
-				}
-				else
-				{
-					return None;
-				}
-			}
+	let king_to = castle_king_to(loc, dir);
+	let rook_to = king_to.offset((-dir, 0));
 
-			to = to.offset((dir, 0));
-		}
+	/* Stop if a square that the king or the rook crosses or ends up on
+	 * is occupied by another piece. This also covers the squares
+	 * between the king and the rook. */
+	let path = between(loc, king_to) | loc_bit(king_to)
+		| between(rook, rook_to) | loc_bit(rook_to);
 
-		/* The rook must be on the castling square, castling with a
-		 * rook that just happens to be next to the king is not
-		 * allowed. */
-		if Some(to) != rook_loc
-		{
-			return None;
-		}
+	if path & !loc_bit(loc) & !loc_bit(rook) & occupied(b) != 0
+	{
+		return None;
 	}
 
-	/* Check whether the king is clear to cross. The king always moves two
-	 * squares. */
-	for x in 0..3
-	{
-		let to = loc.offset((x * dir, 0));
+	/* Stop if the king would move out of, across or into a check. The
+	 * king itself can not hide an attack along the rank, since it would
+	 * be in check on its own square. */
+	let opponent = p.player.opponent();
 
-		/* Stop if castling would move out of the board */
-		if !to.valid()
+	for x in loc.x.min(king_to.x)..=loc.x.max(king_to.x)
+	{
+		if b.is_square_attacked(Loc { x, y: loc.y }, opponent)
 		{
 			return None;
 		}
+	}
 
-		/* Stop if a crossing square is occupied by a non-castling
-		 * piece */
-		if to != loc && Some(to) != rook_loc && b.at(to).occupied()
-		{
-			return None;
-		}
+	/* The rook can hide an attack along the rank on the king's
+	 * destination, as with the king on d1, the rook on b1 and an
+	 * opponent's rook on a1 */
+	let cardinal = b.bitboard(opponent, & ROOK)
+		| b.bitboard(opponent, & QUEEN);
 
-		/* Stop if the king would move across a check */
-		if single_move(b, p, loc, to).board.is_check(p.player)
-		{
-			return None;
-		}
+	if cardinal_attacks(king_to, occupied(b) & !loc_bit(rook)) & cardinal
+		!= 0
+	{
+		return None;
 	}
 
-	rook_loc
+	Some(rook)
 }
 
 pub fn king_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
@@ -313,106 +287,39 @@ pub fn king_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
 	let mut ms = Vec::<Move>::new();
 
 	/* Check normal moves */
-	for dir in
-		[
-			(-1,  1), ( 0,  1), ( 1,  1),
-			(-1,  0),           ( 1,  0),
-			(-1, -1), ( 0, -1), ( 1, -1),
-		]
-	{
-		let to = loc.offset(dir);
+	ms.extend(attack_moves(b, p, loc, king_attacks(loc)));
 
-		if to.valid()
-		{
-			let m = single_move(b, p, loc, to);
+	/* Check castling moves */
+	ms.extend(castle_moves(b, p, loc));
 
-			match b.at(to)
-			{
-				Square::Occupied(q) =>
-				{
-					if p.player == q.player.opponent()
-					{
-						ms.push(m);
-					}
-				},
-				Square::Empty => ms.push(m),
-			}
-		}
-	}
+	ms
+}
 
-	/* Check castling moves */
-	for c in
-		[
-			(b.castling(p.player).k,  1,
-				castle_k_notation
-				as fn(& Move, & Board, & Vec<Move>) -> String),
-			(b.castling(p.player).q, -1,
-				castle_q_notation
-				as fn(& Move, & Board, & Vec<Move>) -> String),
-		]
+pub fn castle_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
+{
+	let mut ms = Vec::<Move>::new();
+
+	for c in [(b.castling(p.player).k, 1), (b.castling(p.player).q, -1)]
 	{
 		if let Some(rook_loc) = check_castle(b, p, loc, c.0, c.1)
 		{
-			let to = loc.offset((2 * c.1, 0));
-			let rook_to = to.offset((-c.1, 0));
+			let to = castle_king_to(loc, c.1);
 
 			let mut m = single_move(b, p, loc, to);
 
-			*m.board.at_mut(rook_to) = *m.board.at(rook_loc);
-			*m.board.at_mut(rook_loc) = Square::Empty;
-	
-			m.notation_fn = c.2;
-
+			m.rook_file = Some(rook_loc.x as u8);
 			m.flags.castle = true;
 
 			ms.push(m);
 		}
 	}
 
-	/* Moving the king gives up all of the player's castling rights */
-	for m in & mut ms
-	{
-		*m.board.castling_mut(p.player) = Castling
-		{
-			k: None,
-			q: None,
-		};
-	}
-
 	ms
 }
 
 pub fn knight_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
 {
-	let mut ms = Vec::<Move>::new();
-
-	for dir in
-		[
-			(-2,  1), (-1,  2), ( 1,  2), ( 2,  1),
-			(-2, -1), (-1, -2), ( 1, -2), ( 2, -1),
-		]
-	{
-		let to = loc.offset(dir);
-
-		if to.valid()
-		{
-			let m = single_move(b, p, loc, to);
-
-			match b.at(to)
-			{
-				Square::Occupied(q) =>
-				{
-					if p.player == q.player.opponent()
-					{
-						ms.push(m);
-					}
-				},
-				Square::Empty => ms.push(m),
-			}
-		}
-	}
-
-	ms
+	attack_moves(b, p, loc, knight_attacks(loc))
 }
 
 pub fn pawn_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
@@ -436,7 +343,9 @@ pub fn pawn_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
 		if jump_loc.valid() && loc.y == start && b.at(jump_loc).empty()
 		{
 			let mut m = single_move(b, p, loc, jump_loc);
-			m.board.passant = Some(step_loc);
+
+			m.flags.double_push = true;
+
 			ms.push(m);
 		}
 	}
@@ -454,13 +363,10 @@ pub fn pawn_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
 					ms.push(single_move(b, p, loc, to));
 				}
 			}
-			else if Some(to) == b.passant
+			else if Some(to) == b.passant()
 			{
 				let mut m = single_move(b, p, loc, to);
 
-				let s = m.board.at_mut(to.offset((0, -dir)));
-				*s = Square::Empty;
-
 				m.flags.capture = true;
 				m.flags.passant = true;
 
@@ -476,12 +382,91 @@ pub fn pawn_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
 		ms = multi_to_promo(& ms);
 	}
 
-	/* Change the notation function to the special pawn notation function
-	 */
-	for m in & mut ms
+	ms
+}
+
+/* The squares attacked by the piece on the given location */
+pub fn piece_attacks(b: & Board, p: & Piece, loc: Loc) -> u64
+{
+	match p.kind.index
 	{
-		m.notation_fn = pawn_move_notation;
+		i if i == KING.index	=> king_attacks(loc),
+		i if i == QUEEN.index	=> diagonal_attacks(loc, occupied(b))
+						| cardinal_attacks(loc, occupied(b)),
+		i if i == ROOK.index	=> cardinal_attacks(loc, occupied(b)),
+		i if i == BISHOP.index	=> diagonal_attacks(loc, occupied(b)),
+		i if i == KNIGHT.index	=> knight_attacks(loc),
+		_			=> pawn_attacks(p.player, loc),
 	}
+}
 
-	ms
+/* Enumerate the captures and promotions of the piece on the given location,
+ * including illegal ones */
+pub fn capture_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
+{
+	if p.is_kind(& PAWN)
+	{
+		let mut ms = pawn_moves(b, p, loc);
+
+		ms.retain(|m| m.is_capture() || m.is_promotion().is_some());
+
+		ms
+	}
+	else
+	{
+		attack_moves(b, p, loc, piece_attacks(b, p, loc)
+					& b.occupancy(p.player.opponent()))
+	}
+}
+
+/* Enumerate the moves of the piece on the given location that neither
+ * capture nor promote, including illegal ones */
+pub fn quiet_moves(b: & Board, p: & Piece, loc: Loc) -> Vec<Move>
+{
+	if p.is_kind(& PAWN)
+	{
+		let mut ms = pawn_moves(b, p, loc);
+
+		ms.retain(|m| !m.is_capture() && m.is_promotion().is_none());
+
+		ms
+	}
+	else
+	{
+		let mut ms = attack_moves(b, p, loc, piece_attacks(b, p, loc)
+					& !occupied(b));
+
+		if p.is_kind(& KING)
+		{
+			ms.extend(castle_moves(b, p, loc));
+		}
+
+		ms
+	}
+}
+
+/* Enumerate the moves of the piece on the given location that may get its
+ * king out of a check by a single piece on the checker location, by
+ * capturing the checker or moving onto one of the blocking squares. King
+ * moves are not included. */
+pub fn blocking_moves(b: & Board, p: & Piece, loc: Loc, checker: Loc,
+			blocks: u64) -> Vec<Move>
+{
+	if p.is_kind(& PAWN)
+	{
+		let mut ms = pawn_moves(b, p, loc);
+
+		/* A passant capture takes the checker when it is the pawn that
+		 * just moved two squares */
+		ms.retain(|m| (loc_bit(m.to) & (blocks | loc_bit(checker)) != 0)
+			|| (m.is_passant() && m.to.offset((0, m.from.y - m.to.y))
+				== checker));
+
+		ms
+	}
+	else
+	{
+		attack_moves(b, p, loc, piece_attacks(b, p, loc)
+					& (blocks | loc_bit(checker)))
+	}
 }
@@ -1,13 +1,16 @@
 use crate::board::*;
+use crate::piece::*;
 use crate::r#move::*;
 
 /* Check if the given move needs to be ambiguated from the given set of moves
  * by rank, file, or both. */
-fn disambiguate(m: & Move, ms: & Vec<Move>) -> (bool, bool)
+fn disambiguate(m: & Move, b: & Board, ms: & Vec<Move>) -> (bool, bool)
 {
+	let piece = m.piece(b);
+
 	/* Diagonal pawn moves always specify the departure file, and never
 	 * need to be disambiguated further. */
-	if m.piece.kind.name == "" && m.from.x != m.to.x
+	if piece.kind.name.is_empty() && m.from.x != m.to.x
 	{
 		return (true, false);
 	}
@@ -20,7 +23,7 @@ fn disambiguate(m: & Move, ms: & Vec<Move>) -> (bool, bool)
 		if n.from.x == m.from.x
 			&& n.from.y == m.from.y
 			&& n.to.x == m.to.x
-			&& n.to.y == n.to.y
+			&& n.to.y == m.to.y
 		{
 			continue;
 		}
@@ -29,7 +32,7 @@ fn disambiguate(m: & Move, ms: & Vec<Move>) -> (bool, bool)
 		 * different kind of piece. */
 		if n.to.x != m.to.x
 			|| n.to.y != m.to.y
-			|| !n.piece.is_kind(m.piece.kind)
+			|| !n.piece(b).is_kind(piece.kind)
 		{
 			continue;
 		}
@@ -53,11 +56,13 @@ fn disambiguate(m: & Move, ms: & Vec<Move>) -> (bool, bool)
 }
 
 /* Add a + for checking moves, # for checkmating moves. */
-fn add_check_suffix(mut s: String, m: & Move) -> String
+fn add_check_suffix(mut s: String, m: & Move, b: & Board) -> String
 {
-	if m.board.is_check(m.board.player)
+	let a = b.after_move(m);
+
+	if a.is_check(a.player())
 	{
-		if m.board.moves(m.board.player).len() == 0
+		if a.moves(a.player()).is_empty()
 		{
 			s.push('#');
 		}
@@ -81,29 +86,28 @@ pub fn default_move_notation(m: & Move, b: & Board, ms: & Vec<Move>) -> String
 
 	let d =
 	{
-		let d = disambiguate(m, & ms);
+		let d = disambiguate(m, b, ms);
 
 		m.from.notation(d.0, d.1)
 	};
 
-	let p = match m.board.at(m.to)
+	let p = match m.is_promotion()
 	{
-		Square::Occupied(p) if !p.is(m.piece.player, m.piece.kind)
-			=> format!("={}", p.kind.name),
-		_ => String::from(""),
+		Some(k) => format!("={}", k.name),
+		None => String::from(""),
 	};
 
 	let s = format!("{}{}{}{}{}",
-			m.piece.kind.name,
+			m.piece(b).kind.name,
 			d,
 			x,
 			m.to.notation(true, true),
 			p);
 
-	add_check_suffix(s, m)
+	add_check_suffix(s, m, b)
 }
 
-pub fn pawn_move_notation(m: & Move, _: & Board, _: & Vec<Move>) -> String
+pub fn pawn_move_notation(m: & Move, b: & Board) -> String
 {
 	let x = match m.is_capture()
 	{
@@ -113,11 +117,10 @@ pub fn pawn_move_notation(m: & Move, _: & Board, _: & Vec<Move>) -> String
 
 	let d = m.from.notation(m.is_capture(), false);
 
-	let p = match m.board.at(m.to)
+	let p = match m.is_promotion()
 	{
-		Square::Occupied(p) if !p.is(m.piece.player, m.piece.kind)
-			=> format!("={}", p.kind.name),
-		_ => String::from(""),
+		Some(k) => format!("={}", k.name),
+		None => String::from(""),
 	};
 
 	let s = format!("{}{}{}{}",
@@ -126,15 +129,248 @@ pub fn pawn_move_notation(m: & Move, _: & Board, _: & Vec<Move>) -> String
 			m.to.notation(true, true),
 			p);
 
-	add_check_suffix(s, m)
+	add_check_suffix(s, m, b)
+}
+
+pub fn castle_move_notation(m: & Move, b: & Board) -> String
+{
+	/* Castling with the rook on the king's side of the board is O-O */
+	let s = match m.rook()
+	{
+		Some(rook) if rook.x > m.from.x => "O-O",
+		_ => "O-O-O",
+	};
+
+	add_check_suffix(String::from(s), m, b)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SanError
+{
+	/* The string is not a move in standard algebraic notation */
+	Unknown,
+	/* No legal move matches the notation */
+	Illegal,
+	/* Several legal moves match the notation */
+	Ambiguous,
+}
+
+impl std::fmt::Display for SanError
+{
+	fn fmt(self: & Self, f: & mut std::fmt::Formatter) -> std::fmt::Result
+	{
+		match self
+		{
+			Self::Unknown	=> write!(f, "unknown move"),
+			Self::Illegal	=> write!(f, "illegal move"),
+			Self::Ambiguous	=> write!(f, "ambiguous move"),
+		}
+	}
+}
+
+impl std::error::Error for SanError {}
+
+fn parse_kind(c: char) -> Option<& 'static PieceKind>
+{
+	match c
+	{
+		'K' => Some(& KING),
+		'Q' => Some(& QUEEN),
+		'R' => Some(& ROOK),
+		'B' => Some(& BISHOP),
+		'N' => Some(& KNIGHT),
+		_   => None,
+	}
+}
+
+/* Find the legal move on the board that the given move in standard algebraic
+ * notation refers to. Check and annotation suffixes are ignored, and castling
+ * may be written with either letter O or digit zero. */
+pub fn parse_san(b: & Board, san: & str) -> Result<Move, SanError>
+{
+	let s = san.trim().trim_end_matches(['+', '#', '!', '?']);
+
+	let ms = b.moves(b.player());
+
+	/* Check castling moves */
+	let castle = match s
+	{
+		"O-O"	| "0-0"		=> Some(1),
+		"O-O-O"	| "0-0-0"	=> Some(-1),
+		_			=> None,
+	};
+
+	if let Some(dir) = castle
+	{
+		return ms.into_iter()
+			.find(|m| m.rook().is_some_and(|r| (r.x - m.from.x) * dir > 0))
+			.ok_or(SanError::Illegal);
+	}
+
+	let mut cs = s.chars().collect::<Vec<char>>();
+
+	/* Split off the kind of the moving piece, pawns have none */
+	let kind = match cs.first().and_then(|c| parse_kind(*c))
+	{
+		Some(k) =>
+		{
+			cs.remove(0);
+
+			k
+		},
+		None => & PAWN,
+	};
+
+	/* Split off the promotion kind */
+	let mut promo = None;
+
+	if let Some(k) = cs.last().and_then(|c| parse_kind(*c))
+	{
+		if std::ptr::eq(k, & KING) || !std::ptr::eq(kind, & PAWN)
+		{
+			return Err(SanError::Unknown);
+		}
+
+		cs.pop();
+
+		if cs.last() == Some(& '=')
+		{
+			cs.pop();
+		}
+
+		promo = Some(k);
+	}
+
+	/* Split off the destination square */
+	if cs.len() < 2
+	{
+		return Err(SanError::Unknown);
+	}
+
+	let to = Loc::parse(cs.split_off(cs.len() - 2)
+			.iter().collect::<String>().as_str())
+		.ok_or(SanError::Unknown)?;
+
+	if cs.last() == Some(& 'x')
+	{
+		cs.pop();
+	}
+
+	/* What remains is the file and/or rank of the departure square */
+	let (file, rank) = match cs[..]
+	{
+		[] => (None, None),
+		[f @ 'a'..='h'] => (Some(f as i32 - 'a' as i32), None),
+		[r @ '1'..='8'] => (None, Some(r as i32 - '1' as i32)),
+		[f @ 'a'..='h', r @ '1'..='8'] =>
+			(Some(f as i32 - 'a' as i32), Some(r as i32 - '1' as i32)),
+		_ => return Err(SanError::Unknown),
+	};
+
+	let mut found = ms.into_iter().filter(|m|
+		!m.is_castle()
+		&& m.piece(b).is_kind(kind)
+		&& m.to == to
+		&& file.is_none_or(|x| m.from.x == x)
+		&& rank.is_none_or(|y| m.from.y == y)
+		&& match (m.is_promotion(), promo)
+		{
+			(Some(k), Some(l)) => std::ptr::eq(k, l),
+			(None, None) => true,
+			_ => false,
+		});
+
+	match (found.next(), found.next())
+	{
+		(Some(m), None) => Ok(m),
+		(Some(_), Some(_)) => Err(SanError::Ambiguous),
+		(None, _) => Err(SanError::Illegal),
+	}
+}
+
+/* Write the move in UCI notation. A castling move in which the king stays on
+ * its square is always written as the king capturing its own rook, since it
+ * would otherwise look like a null move. */
+pub fn uci_move_notation(m: & Move, chess960: bool) -> String
+{
+	let to = match m.rook()
+	{
+		Some(rook) if chess960 || m.from == m.to => rook,
+		_ => m.to,
+	};
+
+	let p = m.is_promotion().map_or(String::new(), |k| k.name.to_lowercase());
+
+	format!("{}{}{}", m.from.notation(true, true), to.notation(true, true), p)
 }
 
-pub fn castle_k_notation(m: & Move, _: & Board, _: & Vec<Move>) -> String
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum UciError
 {
-	add_check_suffix(String::from("O-O"), m)
+	/* The string is not a move in UCI notation */
+	Unknown,
+	/* No legal move matches the notation */
+	Illegal,
+}
+
+impl std::fmt::Display for UciError
+{
+	fn fmt(self: & Self, f: & mut std::fmt::Formatter) -> std::fmt::Result
+	{
+		match self
+		{
+			Self::Unknown	=> write!(f, "unknown move"),
+			Self::Illegal	=> write!(f, "illegal move"),
+		}
+	}
 }
 
-pub fn castle_q_notation(m: & Move, _: & Board, _: & Vec<Move>) -> String
+impl std::error::Error for UciError {}
+
+/* Find the legal move on the board that the given move in UCI notation refers
+ * to. Castling moves may be written either as the king moving to its
+ * destination square, or as the king capturing its own rook. */
+pub fn parse_uci(b: & Board, uci: & str) -> Result<Move, UciError>
 {
-	add_check_suffix(String::from("O-O-O"), m)
+	let (from, to, promo) = match (uci.get(0..2), uci.get(2..4), uci.get(4..))
+	{
+		(Some(from), Some(to), Some(promo)) if promo.len() <= 1 =>
+		(
+			Loc::parse(from).ok_or(UciError::Unknown)?,
+			Loc::parse(to).ok_or(UciError::Unknown)?,
+			match promo
+			{
+				""  => None,
+				"q" => Some(& QUEEN),
+				"r" => Some(& ROOK),
+				"b" => Some(& BISHOP),
+				"n" => Some(& KNIGHT),
+				_   => return Err(UciError::Unknown),
+			},
+		),
+		_ => return Err(UciError::Unknown),
+	};
+
+	let ms = b.moves(b.player());
+
+	/* Prefer ordinary moves, a king moving two squares in Chess960 could
+	 * be either an ordinary move or castling. */
+	let normal = ms.iter().find(|m|
+		!m.is_castle()
+		&& m.from == from
+		&& m.to == to
+		&& match (m.is_promotion(), promo)
+		{
+			(Some(k), Some(l)) => std::ptr::eq(k, l),
+			(None, None) => true,
+			_ => false,
+		});
+
+	let castle = ms.iter().find(|m|
+		m.is_castle()
+		&& promo.is_none()
+		&& m.from == from
+		&& (m.to == to || m.rook() == Some(to)));
+
+	normal.or(castle).copied().ok_or(UciError::Illegal)
 }
@@ -0,0 +1,290 @@
+use crate::bitboard::*;
+use crate::board::*;
+use crate::r#move::*;
+use crate::packed_move::*;
+use crate::piece::*;
+use crate::player::*;
+
+/* Values of the kinds of pieces in centipawns, indexed by PieceKind::index */
+const VALUES: [i32; 6] = [20000, 900, 500, 330, 320, 100];
+
+/* The kinds of pieces from the least to the most valuable */
+static CHEAPEST: [& PieceKind; 6] =
+	[& PAWN, & KNIGHT, & BISHOP, & ROOK, & QUEEN, & KING];
+
+fn value(kind: & PieceKind) -> i32
+{
+	VALUES[kind.index]
+}
+
+/* Estimate the material won by the move when both players keep capturing
+ * on its destination square with their least valuable piece, each stopping
+ * when it no longer pays off. */
+pub fn static_exchange(b: & Board, m: & Move) -> i32
+{
+	let mut occupied = b.occupancy(Player::White)
+		| b.occupancy(Player::Black);
+
+	let captured = match b.at(m.to)
+	{
+		Square::Occupied(p) => value(p.kind),
+		Square::Empty if m.is_passant() => value(& PAWN),
+		Square::Empty => 0,
+	};
+
+	/* The value of the piece that stands on the square after each
+	 * capture */
+	let mut piece = value(m.piece(b).kind);
+	let mut gain = vec![captured];
+
+	if let Some(k) = m.is_promotion()
+	{
+		piece = value(k);
+		gain[0] += value(k) - value(& PAWN);
+	}
+
+	occupied &= !loc_bit(m.from);
+
+	if m.is_passant()
+	{
+		occupied &= !loc_bit(Loc { x: m.to.x, y: m.from.y });
+	}
+
+	let mut player = b.player().opponent();
+
+	loop
+	{
+		let a = attackers(b, m.to, player, occupied);
+
+		let kind = match CHEAPEST.iter()
+			.find(|k| a & b.bitboard(player, k) != 0)
+		{
+			Some(kind) => *kind,
+			None => break,
+		};
+
+		/* The king can not capture onto a defended square */
+		if std::ptr::eq(kind, & KING)
+			&& attackers(b, m.to, player.opponent(), occupied) != 0
+		{
+			break;
+		}
+
+		gain.push(piece - gain[gain.len() - 1]);
+		piece = value(kind);
+
+		let from = a & b.bitboard(player, kind);
+		occupied &= !(from & from.wrapping_neg());
+
+		player = player.opponent();
+	}
+
+	/* Either player may stop capturing when that is better */
+	while gain.len() > 1
+	{
+		let g = gain.pop().unwrap();
+		let last = gain.len() - 1;
+
+		gain[last] = -(-gain[last]).max(g);
+	}
+
+	gain[0]
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum MoveStage
+{
+	HashMove,
+	WinningCaptures,
+	Killers,
+	Quiets,
+	LosingCaptures,
+	Done,
+}
+
+/* Iterate over the legal moves of the player who has the turn, ordered for
+ * search: the hash move, captures that do not lose material, the killer
+ * moves, the quiet moves, and finally captures that lose material. The
+ * moves of each stage are only generated once the previous stages are
+ * exhausted, and each move is only checked for legality when it is
+ * reached. */
+pub struct MovePicker
+{
+	board		: Board,
+	scratch		: Board,
+	hash_move	: Option<PackedMove>,
+	killers		: Vec<PackedMove>,
+	stage		: MoveStage,
+	/* The moves of the current stage, next move last */
+	moves		: Vec<Move>,
+	losing		: Vec<Move>,
+}
+
+impl MovePicker
+{
+	pub fn new(board: & Board, hash_move: Option<PackedMove>,
+			killers: & [PackedMove]) -> Self
+	{
+		let mut picker = Self
+		{
+			board		: *board,
+			scratch		: *board,
+			hash_move	: None,
+			killers		: Vec::new(),
+			stage		: MoveStage::HashMove,
+			moves		: Vec::new(),
+			losing		: Vec::new(),
+		};
+
+		/* A hash move that does not fit the board is ignored */
+		if let Some(m) = hash_move.and_then(|h| picker.lookup(h))
+		{
+			picker.hash_move = Some(m.pack());
+			picker.moves.push(m);
+		}
+
+		for k in killers
+		{
+			if !picker.killers.contains(k) && Some(*k) != picker.hash_move
+			{
+				picker.killers.push(*k);
+			}
+		}
+
+		picker
+	}
+
+	/* The stage of the move that was returned last */
+	pub fn stage(self: & Self) -> MoveStage
+	{
+		self.stage
+	}
+
+	/* Find the move of the piece on its departure square that the packed
+	 * move refers to, if it can be made according to the rules of piece
+	 * movement */
+	fn lookup(self: & Self, packed: PackedMove) -> Option<Move>
+	{
+		match self.board.at(packed.from())
+		{
+			Square::Occupied(p) if p.is_player(self.board.player()) =>
+				p.moves(& self.board, packed.from()).into_iter()
+					.find(|m| m.pack() == packed),
+			_ => None,
+		}
+	}
+
+	/* Check whether the move was already returned by an earlier stage */
+	fn seen(self: & Self, m: & Move) -> bool
+	{
+		let p = m.pack();
+
+		self.hash_move == Some(p)
+			|| (!m.is_capture() && m.is_promotion().is_none()
+				&& self.killers.contains(& p))
+	}
+
+	/* Fill the moves with those of the next stage */
+	fn next_stage(self: & mut Self)
+	{
+		self.stage = match self.stage
+		{
+			MoveStage::HashMove =>
+			{
+				let b = & self.board;
+
+				let mut scored = generate_moves(b, b.player(),
+						|p, loc| capture_moves(b, p, loc))
+					.into_iter()
+					.filter(|m| !self.seen(m))
+					.map(|m| (static_exchange(b, & m), m))
+					.collect::<Vec<(i32, Move)>>();
+
+				/* Best first, ties in order of generation */
+				scored.sort_by_key(|(s, _)| -s);
+
+				for (s, m) in scored.into_iter().rev()
+				{
+					if s < 0
+					{
+						self.losing.push(m);
+					}
+					else
+					{
+						self.moves.push(m);
+					}
+				}
+
+				MoveStage::WinningCaptures
+			},
+			MoveStage::WinningCaptures =>
+			{
+				self.moves = self.killers.iter().rev()
+					.filter_map(|k| self.lookup(*k))
+					.filter(|m| !m.is_capture()
+						&& m.is_promotion().is_none())
+					.collect();
+
+				MoveStage::Killers
+			},
+			MoveStage::Killers =>
+			{
+				let b = & self.board;
+
+				self.moves = generate_moves(b, b.player(),
+						|p, loc| quiet_moves(b, p, loc))
+					.into_iter()
+					.filter(|m| !self.seen(m))
+					.rev()
+					.collect();
+
+				MoveStage::Quiets
+			},
+			MoveStage::Quiets =>
+			{
+				self.moves = std::mem::take(& mut self.losing);
+
+				MoveStage::LosingCaptures
+			},
+			MoveStage::LosingCaptures | MoveStage::Done =>
+				MoveStage::Done,
+		};
+	}
+
+	fn is_legal(self: & mut Self, m: & Move) -> bool
+	{
+		let player = self.board.player();
+		let undo = self.scratch.make_move(m);
+		let legal = !self.scratch.is_check(player);
+
+		self.scratch.unmake_move(m, & undo);
+
+		legal
+	}
+}
+
+impl Iterator for MovePicker
+{
+	type Item = Move;
+
+	fn next(& mut self) -> Option<Move>
+	{
+		loop
+		{
+			while let Some(m) = self.moves.pop()
+			{
+				if self.is_legal(& m)
+				{
+					return Some(m);
+				}
+			}
+
+			if self.stage == MoveStage::Done
+			{
+				return None;
+			}
+
+			self.next_stage();
+		}
+	}
+}
@@ -0,0 +1,190 @@
+use crate::bitboard::*;
+use crate::board::*;
+use crate::piece::*;
+use crate::r#move::*;
+
+/* A move packed into 16 bits: the departure square in bits 0-5, the
+ * destination square in bits 6-11, and a code for the kind of move in bits
+ * 12-15. Squares are numbered (y * 8 + x). The codes are:
+ *
+ *	0	quiet move
+ *	1	double pawn push
+ *	2	castling with the rook on the king's side of the board (O-O)
+ *	3	castling with the rook on the queen's side of the board (O-O-O)
+ *	4	capture
+ *	5	passant capture
+ *	8-11	promotion to knight, bishop, rook or queen
+ *	12-15	capturing promotion to knight, bishop, rook or queen
+ */
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct PackedMove(u16);
+
+const QUIET		: u16 = 0;
+const DOUBLE_PUSH	: u16 = 1;
+const CASTLE_K		: u16 = 2;
+const CASTLE_Q		: u16 = 3;
+const CAPTURE		: u16 = 4;
+const PASSANT		: u16 = 5;
+const PROMOTION		: u16 = 8;
+
+fn promotion_kind(code: u16) -> & 'static PieceKind
+{
+	[& KNIGHT, & BISHOP, & ROOK, & QUEEN][(code & 3) as usize]
+}
+
+fn promotion_code(kind: & PieceKind) -> u16
+{
+	if std::ptr::eq(kind, & KNIGHT)
+	{
+		0
+	}
+	else if std::ptr::eq(kind, & BISHOP)
+	{
+		1
+	}
+	else if std::ptr::eq(kind, & ROOK)
+	{
+		2
+	}
+	else
+	{
+		3
+	}
+}
+
+impl PackedMove
+{
+	pub fn new(m: & Move) -> Self
+	{
+		let code = if m.is_castle()
+		{
+			match m.rook()
+			{
+				Some(rook) if rook.x > m.from.x => CASTLE_K,
+				_ => CASTLE_Q,
+			}
+		}
+		else if let Some(kind) = m.is_promotion()
+		{
+			let capture = if m.is_capture() { CAPTURE } else { 0 };
+
+			PROMOTION | capture | promotion_code(kind)
+		}
+		else if m.is_passant()
+		{
+			PASSANT
+		}
+		else if m.is_capture()
+		{
+			CAPTURE
+		}
+		else if m.flags.double_push
+		{
+			DOUBLE_PUSH
+		}
+		else
+		{
+			QUIET
+		};
+
+		let from = (m.from.y * 8 + m.from.x) as u16;
+		let to = (m.to.y * 8 + m.to.x) as u16;
+
+		Self(from | to << 6 | code << 12)
+	}
+
+	pub fn from_bits(bits: u16) -> Self
+	{
+		Self(bits)
+	}
+
+	pub fn bits(self: & Self) -> u16
+	{
+		self.0
+	}
+
+	pub fn from(self: & Self) -> Loc
+	{
+		bit_loc((self.0 & 0x3f) as u32)
+	}
+
+	pub fn to(self: & Self) -> Loc
+	{
+		bit_loc((self.0 >> 6 & 0x3f) as u32)
+	}
+
+	fn code(self: & Self) -> u16
+	{
+		self.0 >> 12
+	}
+
+	/* Convert the packed move to a move of the player who has the turn on
+	 * the given board. Returns None if the packed move does not fit the
+	 * board, such as when there is no piece to move, or when a capture
+	 * has nothing to capture. The move is not checked for legality. */
+	pub fn unpack(self: & Self, board: & Board) -> Option<Move>
+	{
+		let (from, to, code) = (self.from(), self.to(), self.code());
+
+		let piece = match board.at(from)
+		{
+			Square::Occupied(p) if p.is_player(board.player()) => *p,
+			_ => return None,
+		};
+
+		let mut m = single_move(board, & piece, from, to);
+
+		m.flags.double_push = piece.is_kind(& PAWN)
+			&& (to.y - from.y).abs() == 2;
+
+		match code
+		{
+			CASTLE_K | CASTLE_Q =>
+			{
+				let c = board.castling(board.player());
+
+				let rook = if code == CASTLE_K { c.k } else { c.q };
+
+				m.rook_file = rook.map(|r| r.x as u8);
+				m.flags.castle = true;
+				m.flags.capture = false;
+
+				if m.rook_file.is_none() || !piece.is_kind(& KING)
+				{
+					return None;
+				}
+			},
+			6 | 7 => return None,
+			PASSANT =>
+			{
+				m.flags.passant = true;
+				m.flags.capture = true;
+
+				if !piece.is_kind(& PAWN) || board.passant() != Some(to)
+				{
+					return None;
+				}
+			},
+			_ =>
+			{
+				if m.is_capture() != (code & CAPTURE != 0)
+				{
+					return None;
+				}
+
+				if code & PROMOTION != 0
+				{
+					if !piece.is_kind(& PAWN)
+					{
+						return None;
+					}
+
+					m.promotion = Some(promotion_kind(code));
+					m.flags.promotion = true;
+				}
+			},
+		}
+
+		Some(m)
+	}
+}
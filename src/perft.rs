@@ -0,0 +1,256 @@
+use crate::board::*;
+use crate::r#move::*;
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/* A table of node counts of subtrees, keyed by the Zobrist hash of the
+ * position and the depth of the subtree. It can be shared between threads
+ * without locking: each entry stores the key and the count as (key ^ data,
+ * data), so an entry that was torn by two threads writing at once does not
+ * match its key. */
+pub struct PerftTable
+{
+	entries	: Vec<[AtomicU64; 2]>,
+}
+
+impl PerftTable
+{
+	/* A table of at least the given number of entries, rounded up to a
+	 * power of two. Each entry takes 16 bytes. */
+	pub fn new(size: usize) -> Self
+	{
+		let size = size.max(1).next_power_of_two();
+
+		Self
+		{
+			entries: (0..size)
+				.map(|_| [AtomicU64::new(0), AtomicU64::new(0)])
+				.collect(),
+		}
+	}
+
+	fn entry(self: & Self, key: u64) -> & [AtomicU64; 2]
+	{
+		& self.entries[key as usize & (self.entries.len() - 1)]
+	}
+
+	/* The count is stored in the high 56 bits of the data, and the depth
+	 * in the low 8 bits */
+	fn get(self: & Self, key: u64, depth: u32) -> Option<u64>
+	{
+		let e = self.entry(key);
+		let data = e[1].load(Ordering::Relaxed);
+
+		if e[0].load(Ordering::Relaxed) ^ data == key
+			&& data & 0xff == depth as u64
+		{
+			Some(data >> 8)
+		}
+		else
+		{
+			None
+		}
+	}
+
+	fn insert(self: & Self, key: u64, depth: u32, nodes: u64)
+	{
+		let e = self.entry(key);
+		let data = nodes << 8 | depth as u64;
+
+		e[0].store(key ^ data, Ordering::Relaxed);
+		e[1].store(data, Ordering::Relaxed);
+	}
+}
+
+fn count(b: & mut Board, depth: u32, table: Option<& PerftTable>) -> u64
+{
+	if depth == 0
+	{
+		return 1;
+	}
+
+	let ms = b.moves(b.player());
+
+	/* The leaves need not be visited to be counted */
+	if depth == 1
+	{
+		return ms.len() as u64;
+	}
+
+	let key = b.hash();
+
+	if let Some(n) = table.and_then(|t| t.get(key, depth))
+	{
+		return n;
+	}
+
+	let mut n = 0;
+
+	for m in & ms
+	{
+		let undo = b.make_move(m);
+
+		n += count(b, depth - 1, table);
+
+		b.unmake_move(m, & undo);
+	}
+
+	if let Some(t) = table
+	{
+		t.insert(key, depth, n);
+	}
+
+	n
+}
+
+/* Count the positions reached after the given number of halfmoves */
+pub fn perft(board: & Board, depth: u32) -> u64
+{
+	let mut b = *board;
+
+	count(& mut b, depth, None)
+}
+
+/* Count the positions reached after the given number of halfmoves, with the
+ * moves of the player who has the turn divided between the given number of
+ * threads. The counts of subtrees are looked up in and stored into the
+ * table, if one is given. */
+pub fn parallel_perft(board: & Board, depth: u32, threads: usize,
+			table: Option<& PerftTable>) -> u64
+{
+	if depth <= 1 || threads <= 1
+	{
+		let mut b = *board;
+
+		return count(& mut b, depth, table);
+	}
+
+	let ms = board.moves(board.player());
+	let next = AtomicUsize::new(0);
+
+	/* Each thread takes the next move that is yet to be counted */
+	std::thread::scope(|s|
+	{
+		let handles = (0..threads.min(ms.len())).map(|_| s.spawn(||
+		{
+			let mut n = 0;
+
+			loop
+			{
+				let i = next.fetch_add(1, Ordering::Relaxed);
+
+				if i >= ms.len()
+				{
+					break;
+				}
+
+				n += count(& mut board.after_move(& ms[i]), depth - 1,
+					table);
+			}
+
+			n
+		}))
+		.collect::<Vec<_>>();
+
+		handles.into_iter().map(|h| h.join().unwrap()).sum()
+	})
+}
+
+/* The counts of the leaf nodes of a perft by kind, as given in the tables on
+ * the chessprogramming wiki */
+#[derive(Copy, Clone, Default, PartialEq, Debug)]
+pub struct PerftStats
+{
+	pub nodes	: u64,
+	pub captures	: u64,
+	pub passants	: u64,
+	pub castles	: u64,
+	pub promotions	: u64,
+	pub checks	: u64,
+	pub mates	: u64,
+}
+
+impl PerftStats
+{
+	pub fn add(self: & mut Self, other: & PerftStats)
+	{
+		self.nodes += other.nodes;
+		self.captures += other.captures;
+		self.passants += other.passants;
+		self.castles += other.castles;
+		self.promotions += other.promotions;
+		self.checks += other.checks;
+		self.mates += other.mates;
+	}
+}
+
+/* Make the move and count the leaf nodes that are depth - 1 halfmoves
+ * further */
+fn count_stats(b: & mut Board, m: & Move, depth: u32, stats: & mut PerftStats)
+{
+	let undo = b.make_move(m);
+
+	if depth == 1
+	{
+		stats.nodes += 1;
+		stats.captures += m.is_capture() as u64;
+		stats.passants += m.is_passant() as u64;
+		stats.castles += m.is_castle() as u64;
+		stats.promotions += m.is_promotion().is_some() as u64;
+
+		if b.checkers() != 0
+		{
+			stats.checks += 1;
+			stats.mates += b.moves(b.player()).is_empty() as u64;
+		}
+	}
+	else
+	{
+		for n in b.moves(b.player())
+		{
+			count_stats(b, & n, depth - 1, stats);
+		}
+	}
+
+	b.unmake_move(m, & undo);
+}
+
+/* Count the leaf nodes after each of the legal moves of the player who has
+ * the turn, the given number of halfmoves from the board */
+pub fn perft_divide(board: & Board, depth: u32) -> Vec<(Move, PerftStats)>
+{
+	let mut b = *board;
+
+	if depth == 0
+	{
+		return Vec::new();
+	}
+
+	b.moves(b.player()).into_iter().map(|m|
+	{
+		let mut stats = PerftStats::default();
+
+		count_stats(& mut b, & m, depth, & mut stats);
+
+		(m, stats)
+	})
+	.collect()
+}
+
+/* Count the leaf nodes the given number of halfmoves from the board */
+pub fn perft_stats(board: & Board, depth: u32) -> PerftStats
+{
+	let mut total = PerftStats::default();
+
+	if depth == 0
+	{
+		total.nodes = 1;
+	}
+
+	for (_, stats) in perft_divide(board, depth)
+	{
+		total.add(& stats);
+	}
+
+	total
+}
@@ -0,0 +1,1096 @@
+use crate::board::*;
+use crate::game::*;
+use crate::r#move::*;
+use crate::player::*;
+
+/* Maximum length of a line of movetext in export format */
+const LINE_WIDTH: usize = 79;
+
+/* The Seven Tag Roster, and the values to use for missing tags */
+const ROSTER: [(& str, & str); 7] =
+[
+	("Event",	"?"),
+	("Site",	"?"),
+	("Date",	"????.??.??"),
+	("Round",	"?"),
+	("White",	"?"),
+	("Black",	"?"),
+	("Result",	"*"),
+];
+
+/* Return the game termination marker for the state of the game */
+pub fn result_token(game: & Game) -> String
+{
+	if game.state() == State::Playing
+	{
+		return String::from("*");
+	}
+
+	let r = game.score().map(|r| match r
+		{
+			1 => "1/2",
+			2 => "1",
+			_ => "0",
+		});
+
+	format!("{}-{}", r[0], r[1])
+}
+
+/* Format a clock time as h:mm:ss, with tenths of a second if needed */
+fn clock_notation(clock: std::time::Duration) -> String
+{
+	let s = clock.as_secs();
+	let t = clock.subsec_millis() / 100;
+
+	let mut c = format!("{}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60);
+
+	if t != 0
+	{
+		c.push_str(format!(".{}", t).as_str());
+	}
+
+	c
+}
+
+/* Split an annotation into the tokens of a brace comment. Clock times are
+ * embedded in the comment as [%clk] commands. */
+fn comment_tokens(a: & Annotation) -> Vec<String>
+{
+	let mut ts = Vec::<String>::new();
+
+	if let Some(clock) = a.clock
+	{
+		ts.push(String::from("[%clk"));
+		ts.push(format!("{}]", clock_notation(clock)));
+	}
+
+	if let Some(comment) = & a.comment
+	{
+		/* A comment can not contain its closing brace */
+		ts.extend(comment.split_whitespace()
+			.map(|w| w.replace('}', ")")));
+	}
+
+	if let Some(t) = ts.first_mut()
+	{
+		t.insert(0, '{');
+	}
+
+	if let Some(t) = ts.last_mut()
+	{
+		t.push('}');
+	}
+
+	ts
+}
+
+/* Make the movetext of the game, excluding the game termination marker, as a
+ * list of tokens. If annotate is set, NAGs and comments are included. */
+pub fn make_movetext(game: & Game, annotate: bool) -> Vec<String>
+{
+	let mut ts = Vec::<String>::new();
+
+	/* A move number must be printed before black's move if it doesn't
+	 * directly follow white's move */
+	let mut number = true;
+
+	if annotate
+	{
+		ts.extend(comment_tokens(game.annotation(0)));
+	}
+
+	let mut b = game.board_at(0);
+
+	for (i, m) in game.played_moves().iter().enumerate()
+	{
+		/* Print the move number */
+		match b.player()
+		{
+			Player::White =>
+				ts.push(format!("{}.", b.fullmove)),
+			Player::Black if number =>
+				ts.push(format!("{}...", b.fullmove)),
+			Player::Black => (),
+		}
+
+		/* Print the move notation */
+		let ms = b.moves(b.player());
+		ts.push(m.notation(& b, & ms));
+
+		number = false;
+
+		if annotate
+		{
+			let a = game.annotation(i as i32 + 1);
+
+			ts.extend(a.nags.iter().map(|n| format!("${}", n)));
+
+			let cs = comment_tokens(a);
+
+			number = !cs.is_empty();
+			ts.extend(cs);
+		}
+
+		b.make_move(m);
+	}
+
+	ts
+}
+
+/* Escape a tag value for use in a tag pair */
+fn tag_value(s: & str) -> String
+{
+	s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+pub fn make_pgn(game: & Game) -> String
+{
+	let mut pgn = String::new();
+
+	/* Print the Seven Tag Roster in order, followed by the setup tags and
+	 * any other tags in the order they were set. */
+	let mut tags = Vec::<(String, String)>::new();
+
+	for (name, value) in ROSTER
+	{
+		let value = match name
+		{
+			"Result" => result_token(game),
+			_ => String::from(game.tag(name).unwrap_or(value)),
+		};
+
+		tags.push((String::from(name), value));
+	}
+
+	/* A game that was set up from the standard starting position needs
+	 * no setup tags */
+	if game.is_setup() && game.board_at(0).fen() != Board::default().fen()
+	{
+		tags.push((String::from("SetUp"), String::from("1")));
+		tags.push((String::from("FEN"), game.board_at(0).fen()));
+	}
+
+	for (name, value) in game.tags()
+	{
+		if ROSTER.iter().all(|r| r.0 != name)
+			&& name != "SetUp" && name != "FEN"
+		{
+			tags.push((name.clone(), value.clone()));
+		}
+	}
+
+	for (name, value) in tags
+	{
+		pgn.push_str(format!("[{} \"{}\"]\n", name, tag_value(& value))
+			.as_str());
+	}
+
+	pgn.push('\n');
+
+	/* Print the movetext, wrapping the lines */
+	let mut ts = make_movetext(game, true);
+	ts.push(result_token(game));
+
+	let mut n = 0;
+
+	for t in ts
+	{
+		if n != 0 && n + 1 + t.len() > LINE_WIDTH
+		{
+			pgn.push('\n');
+			n = 0;
+		}
+		else if n != 0
+		{
+			pgn.push(' ');
+			n += 1;
+		}
+
+		pgn.push_str(t.as_str());
+		n += t.len();
+	}
+
+	pgn.push('\n');
+
+	pgn
+}
+
+#[derive(Clone, Debug)]
+pub struct PgnError
+{
+	pub line	: usize,
+	pub column	: usize,
+	pub token	: String,
+	pub reason	: String,
+}
+
+impl std::fmt::Display for PgnError
+{
+	fn fmt(self: & Self, f: & mut std::fmt::Formatter) -> std::fmt::Result
+	{
+		write!(f, "{}:{}: {} at `{}`",
+			self.line, self.column, self.reason, self.token)
+	}
+}
+
+impl std::error::Error for PgnError {}
+
+#[derive(Copy, Clone, PartialEq)]
+enum TokenKind
+{
+	Symbol,
+	MoveNumber,
+	Result,
+	String,
+	Comment,
+	Nag,
+	Open,
+	Close,
+	TagOpen,
+	TagClose,
+}
+
+#[derive(Clone)]
+struct Token
+{
+	kind	: TokenKind,
+	text	: String,
+	line	: usize,
+	column	: usize,
+}
+
+impl Token
+{
+	fn error(self: & Self, reason: & str) -> PgnError
+	{
+		PgnError
+		{
+			line	: self.line,
+			column	: self.column,
+			token	: self.text.clone(),
+			reason	: String::from(reason),
+		}
+	}
+}
+
+struct Lexer<'a>
+{
+	ci	: std::iter::Peekable<std::str::Chars<'a>>,
+	line	: usize,
+	column	: usize,
+}
+
+impl<'a> Lexer<'a>
+{
+	fn new(pgn: & 'a str) -> Self
+	{
+		Lexer
+		{
+			ci	: pgn.chars().peekable(),
+			line	: 1,
+			column	: 1,
+		}
+	}
+
+	fn next_char(self: & mut Self) -> Option<char>
+	{
+		let c = self.ci.next()?;
+
+		if c == '\n'
+		{
+			self.line += 1;
+			self.column = 1;
+		}
+		else
+		{
+			self.column += 1;
+		}
+
+		Some(c)
+	}
+
+	fn error(line: usize, column: usize, token: String, reason: & str)
+		-> PgnError
+	{
+		PgnError
+		{
+			line,
+			column,
+			token,
+			reason	: String::from(reason),
+		}
+	}
+
+	fn is_symbol_char(c: char) -> bool
+	{
+		c.is_ascii_alphanumeric() || "_+#=:-/!?".contains(c)
+	}
+
+	fn next_token(self: & mut Self) -> Result<Option<Token>, PgnError>
+	{
+		/* Skip whitespace, rest of line comments, and escaped lines */
+		loop
+		{
+			match self.ci.peek()
+			{
+				Some(c) if c.is_whitespace() =>
+				{
+					self.next_char();
+				},
+				Some('%') if self.column == 1 =>
+				{
+					while !matches!(self.next_char(),
+							Some('\n') | None) {}
+				},
+				Some(';') =>
+				{
+					while !matches!(self.next_char(),
+							Some('\n') | None) {}
+				},
+				_ => break,
+			}
+		}
+
+		let (line, column) = (self.line, self.column);
+
+		let c = match self.next_char()
+		{
+			Some(c) => c,
+			None => return Ok(None),
+		};
+
+		let mut text = String::new();
+
+		let kind = match c
+		{
+			'[' => TokenKind::TagOpen,
+			']' => TokenKind::TagClose,
+			'(' => TokenKind::Open,
+			')' => TokenKind::Close,
+			'"' =>
+			{
+				loop
+				{
+					match self.next_char()
+					{
+						Some('"') => break,
+						/* Backslashes escape the next
+						 * character */
+						Some('\\') if self.ci.peek().is_some()
+							=> text.extend(self.next_char()),
+						Some(c) => text.push(c),
+						None => return Err(Self::error(
+							line, column, text,
+							"unterminated string")),
+					}
+				}
+
+				TokenKind::String
+			},
+			'{' =>
+			{
+				loop
+				{
+					match self.next_char()
+					{
+						Some('}') => break,
+						Some(c) => text.push(c),
+						None => return Err(Self::error(
+							line, column, text,
+							"unterminated comment")),
+					}
+				}
+
+				TokenKind::Comment
+			},
+			'$' =>
+			{
+				while let Some(c) = self.ci.peek()
+				{
+					if !c.is_ascii_digit()
+					{
+						break;
+					}
+
+					text.push(*c);
+					self.next_char();
+				}
+
+				TokenKind::Nag
+			},
+			'*' =>
+			{
+				text.push(c);
+
+				TokenKind::Result
+			},
+			c if Self::is_symbol_char(c) =>
+			{
+				text.push(c);
+
+				while let Some(c) = self.ci.peek()
+				{
+					if !Self::is_symbol_char(*c)
+					{
+						break;
+					}
+
+					text.push(*c);
+					self.next_char();
+				}
+
+				/* A number followed by periods is a move number
+				 * indication */
+				if text.chars().all(|c| c.is_ascii_digit())
+					&& matches!(self.ci.peek(), Some('.'))
+				{
+					while matches!(self.ci.peek(), Some('.'))
+					{
+						self.next_char();
+					}
+
+					TokenKind::MoveNumber
+				}
+				else if ["1-0", "0-1", "1/2-1/2"]
+					.contains(& text.as_str())
+				{
+					TokenKind::Result
+				}
+				else
+				{
+					TokenKind::Symbol
+				}
+			},
+			'.' =>
+			{
+				/* Stray periods, e.g. after a move number
+				 * separated by a space, are ignored */
+				return self.next_token();
+			},
+			c => return Err(Self::error(line, column,
+					String::from(c), "unexpected character")),
+		};
+
+		if text.is_empty()
+		{
+			text.push(c);
+		}
+
+		Ok(Some(Token { kind, text, line, column }))
+	}
+}
+
+/* Split a trailing suffix annotation, such as !? off of a move, and return the
+ * equivalent NAG. */
+fn split_suffix(san: & str) -> (& str, Option<u8>)
+{
+	let n = san.trim_end_matches(['!', '?']);
+
+	let nag = match & san[n.len()..]
+	{
+		"!"	=> Some(1),
+		"?"	=> Some(2),
+		"!!"	=> Some(3),
+		"??"	=> Some(4),
+		"!?"	=> Some(5),
+		"?!"	=> Some(6),
+		_	=> None,
+	};
+
+	(n, nag)
+}
+
+/* Find the legal move on the board that the given SAN move refers to */
+fn find_move(board: & Board, token: & Token) -> Result<Move, PgnError>
+{
+	board.parse_san(token.text.as_str())
+		.map_err(|e| token.error(e.to_string().as_str()))
+}
+
+/* Parse a comment, with any [%clk] command, into the given annotation */
+fn parse_comment(token: & Token, a: & mut Annotation)
+	-> Result<(), PgnError>
+{
+	let mut text = token.text.clone();
+
+	if let Some(i) = text.find("[%clk")
+	{
+		let j = match text[i..].find(']')
+		{
+			Some(j) => i + j,
+			None => return Err(token.error("invalid clock command")),
+		};
+
+		let clock = parse_clock(text[i + 5..j].trim())
+			.ok_or_else(|| token.error("invalid clock time"))?;
+
+		a.clock = Some(clock);
+		text.replace_range(i..=j, "");
+	}
+
+	let text = text.split_whitespace().collect::<Vec<& str>>().join(" ");
+
+	if !text.is_empty()
+	{
+		a.comment = match & a.comment
+		{
+			Some(c) => Some(format!("{} {}", c, text)),
+			None => Some(text),
+		};
+	}
+
+	Ok(())
+}
+
+/* Parse a clock time of the form h:mm:ss, optionally with fractions of a
+ * second */
+fn parse_clock(s: & str) -> Option<std::time::Duration>
+{
+	let mut secs = 0.0;
+
+	for f in s.split(':')
+	{
+		if f.is_empty() || !f.chars().all(|c| c.is_ascii_digit() || c == '.')
+		{
+			return None;
+		}
+
+		secs = secs * 60.0 + f.parse::<f64>().ok()?;
+	}
+
+	Some(std::time::Duration::from_secs_f64(secs))
+}
+
+struct Parser<'a>
+{
+	lexer	: Lexer<'a>,
+	peeked	: Option<Token>,
+	last	: Token,
+}
+
+impl<'a> Parser<'a>
+{
+	fn next(self: & mut Self) -> Result<Option<Token>, PgnError>
+	{
+		let t = match self.peeked.take()
+		{
+			Some(t) => Some(t),
+			None => self.lexer.next_token()?,
+		};
+
+		if let Some(t) = & t
+		{
+			self.last = t.clone();
+		}
+
+		Ok(t)
+	}
+
+	fn peek(self: & mut Self) -> Result<Option<& Token>, PgnError>
+	{
+		if self.peeked.is_none()
+		{
+			self.peeked = self.lexer.next_token()?;
+		}
+
+		Ok(self.peeked.as_ref())
+	}
+
+	/* Return the next token, which must be of the given kind */
+	fn expect(self: & mut Self, kind: TokenKind, reason: & str)
+		-> Result<Token, PgnError>
+	{
+		match self.next()?
+		{
+			Some(t) if t.kind == kind => Ok(t),
+			Some(t) => Err(t.error(reason)),
+			None => Err(self.last.error(reason)),
+		}
+	}
+
+	fn parse_tags(self: & mut Self)
+		-> Result<Vec<(String, Token)>, PgnError>
+	{
+		let mut tags = Vec::<(String, Token)>::new();
+
+		while matches!(self.peek()?, Some(t) if t.kind == TokenKind::TagOpen)
+		{
+			self.next()?;
+
+			let name = self.expect(TokenKind::Symbol,
+					"expected tag name")?;
+			let value = self.expect(TokenKind::String,
+					"expected tag value")?;
+			self.expect(TokenKind::TagClose, "expected `]`")?;
+
+			tags.push((name.text, value));
+		}
+
+		Ok(tags)
+	}
+
+	/* Parse a recursive variation, which replaces the move played on the
+	 * given board, and check that its moves are legal. */
+	fn parse_variation(self: & mut Self, board: Board)
+		-> Result<(), PgnError>
+	{
+		let mut prev: Option<Board> = None;
+		let mut b = board;
+
+		loop
+		{
+			let t = match self.next()?
+			{
+				Some(t) => t,
+				None => return Err(self.last.error(
+					"unterminated variation")),
+			};
+
+			match t.kind
+			{
+				TokenKind::Symbol =>
+				{
+					let m = find_move(& b, & t)?;
+
+					prev = Some(b);
+					b.make_move(& m);
+				},
+				TokenKind::Open => match prev
+				{
+					Some(p) => self.parse_variation(p)?,
+					None => return Err(t.error(
+						"variation before first move")),
+				},
+				TokenKind::Close => return Ok(()),
+				TokenKind::MoveNumber
+				| TokenKind::Comment
+				| TokenKind::Nag => (),
+				_ => return Err(t.error("unexpected token")),
+			}
+		}
+	}
+
+	/* Parse the movetext of a game, playing the moves in the given game.
+	 * Returns the game termination marker. */
+	fn parse_movetext(self: & mut Self, game: & mut Game)
+		-> Result<Option<Token>, PgnError>
+	{
+		/* The board before the last move, which variations start from */
+		let mut prev: Option<Board> = None;
+
+		loop
+		{
+			let t = match self.next()?
+			{
+				Some(t) => t,
+				None => return Ok(None),
+			};
+
+			let h = game.halfmove();
+
+			match t.kind
+			{
+				TokenKind::Symbol =>
+				{
+					let m = find_move(& game.board(), & t)?;
+
+					prev = Some(game.board());
+					game.play_move(& m);
+
+					let (_, nag) = split_suffix(t.text.as_str());
+
+					if let Some(nag) = nag
+					{
+						game.annotation_mut(h + 1)
+							.nags.push(nag);
+					}
+				},
+				TokenKind::Nag => match t.text.parse::<u8>()
+				{
+					Ok(nag) if h != 0 =>
+						game.annotation_mut(h).nags.push(nag),
+					_ => return Err(t.error("invalid NAG")),
+				},
+				TokenKind::Comment =>
+					parse_comment(& t, game.annotation_mut(h))?,
+				TokenKind::Open => match prev
+				{
+					Some(p) => self.parse_variation(p)?,
+					None => return Err(t.error(
+						"variation before first move")),
+				},
+				TokenKind::MoveNumber => (),
+				TokenKind::Result => return Ok(Some(t)),
+				_ => return Err(t.error("unexpected token")),
+			}
+		}
+	}
+}
+
+/* End the game with the result given by the game termination marker, if it
+ * hasn't already ended on the board. */
+fn apply_result(game: & mut Game, token: & Token) -> Result<(), PgnError>
+{
+	if token.text == "*"
+	{
+		return Ok(());
+	}
+
+	if game.state() != State::Playing
+	{
+		return match result_token(game) == token.text
+		{
+			true => Ok(()),
+			false => Err(token.error(
+				"result does not match the final position")),
+		};
+	}
+
+	/* Results that are not reached on the board count as resignations
+	 * and agreed draws, unless the game was terminated otherwise. */
+	let normal = matches!(game.tag("Termination"), None | Some("normal"));
+	let player = game.player();
+
+	let ok = match (token.text.as_str(), normal)
+	{
+		("1-0", true) => game.resign(Player::Black),
+		("0-1", true) => game.resign(Player::White),
+		("1/2-1/2", _) if game.can_claim_draw() => game.claim_draw(),
+		("1/2-1/2", true) => game.offer_draw(player.opponent())
+					&& game.accept_draw(player),
+		("1-0", false) => game.adjudicate(Some(Player::White)),
+		("0-1", false) => game.adjudicate(Some(Player::Black)),
+		(_, false) => game.adjudicate(None),
+		_ => false,
+	};
+
+	match ok
+	{
+		true => Ok(()),
+		false => Err(token.error("invalid result")),
+	}
+}
+
+pub fn parse_pgn(pgn: & str) -> Result<Game, PgnError>
+{
+	let mut p = Parser
+	{
+		lexer	: Lexer::new(pgn),
+		peeked	: None,
+		last	: Token
+		{
+			kind	: TokenKind::Symbol,
+			text	: String::new(),
+			line	: 1,
+			column	: 1,
+		},
+	};
+
+	let tags = p.parse_tags()?;
+
+	/* Set up the starting board from the FEN tag if there is one */
+	let mut game = match tags.iter().find(|t| t.0 == "FEN")
+	{
+		Some((_, fen)) => match Board::from_fen(fen.text.as_str())
+		{
+			Ok(b) => match Game::try_from_board(b)
+			{
+				Ok(g) => g,
+				Err(es) => return Err(fen.error(
+					format!("illegal position: {}", es[0])
+					.as_str())),
+			},
+			Err(e) => return Err(fen.error(
+				format!("invalid FEN: {}", e).as_str())),
+		},
+		None => Game::new(),
+	};
+
+	for (name, value) in & tags
+	{
+		game.set_tag(name.as_str(), value.text.as_str());
+	}
+
+	if let Some(t) = p.parse_movetext(& mut game)?
+	{
+		apply_result(& mut game, & t)?;
+
+		if let Some(t) = p.next()?
+		{
+			return Err(t.error("unexpected token after result"));
+		}
+	}
+
+	Ok(game)
+}
+
+/* Scan a line of movetext, given whether a brace comment was open at its
+ * start. Returns whether one is still open at its end, and whether a game
+ * termination marker stands outside of the comments. Brace comments do not
+ * nest, and the rest of the line after a semicolon is a comment. */
+fn scan_movetext(l: & str, mut open: bool) -> (bool, bool)
+{
+	let mut text = String::new();
+
+	for c in l.chars()
+	{
+		match c
+		{
+			'}' if open => open = false,
+			'{' if !open => open = true,
+			';' if !open => break,
+			_ if !open => text.push(c),
+			_ => continue,
+		}
+
+		if c == '{' || c == '}'
+		{
+			text.push(' ');
+		}
+	}
+
+	let ended = text.split_whitespace()
+		.any(|t| matches!(t, "1-0" | "0-1" | "1/2-1/2" | "*"));
+
+	(open, ended)
+}
+
+/* Maximum size of the text of a single game read by PgnReader */
+const MAX_GAME_SIZE: usize = 1 << 20;
+
+/* A game that PgnReader could not parse, and the byte offset of its first
+ * line in the input */
+#[derive(Clone, Debug)]
+pub struct PgnSkip
+{
+	pub offset	: u64,
+	pub error	: PgnError,
+}
+
+/* Reads the games of a PGN database one at a time. Only the text of the game
+ * that is being parsed is kept in memory. */
+pub struct PgnReader<R: std::io::BufRead>
+{
+	reader	: R,
+	offset	: u64,
+	line	: usize,
+	pending	: Option<(String, u64, usize)>,
+	done	: bool,
+}
+
+impl<R: std::io::BufRead> PgnReader<R>
+{
+	pub fn new(reader: R) -> Self
+	{
+		PgnReader
+		{
+			reader,
+			offset	: 0,
+			line	: 1,
+			pending	: None,
+			done	: false,
+		}
+	}
+
+	fn io_error(self: & mut Self, e: std::io::Error) -> PgnSkip
+	{
+		self.done = true;
+
+		PgnSkip
+		{
+			offset	: self.offset,
+			error	: PgnError
+			{
+				line	: self.line,
+				column	: 1,
+				token	: String::new(),
+				reason	: e.to_string(),
+			},
+		}
+	}
+
+	/* Read the next line along with its byte offset and line number. PGN
+	 * files are not always valid UTF-8, so invalid characters are
+	 * replaced rather than treated as errors. A line is cut off after
+	 * one byte more than a game may take, so that a line without end
+	 * does not fill the memory, and is still known to be too long. */
+	fn read_line(self: & mut Self)
+		-> Result<Option<(String, u64, usize)>, PgnSkip>
+	{
+		if let Some(l) = self.pending.take()
+		{
+			return Ok(Some(l));
+		}
+
+		if self.done
+		{
+			return Ok(None);
+		}
+
+		let mut buf = Vec::<u8>::new();
+		let mut n = 0;
+
+		loop
+		{
+			let chunk = match self.reader.fill_buf()
+			{
+				Ok(chunk) => chunk,
+				Err(e) if e.kind() == std::io::ErrorKind::Interrupted =>
+					continue,
+				Err(e) => return Err(self.io_error(e)),
+			};
+
+			if chunk.is_empty()
+			{
+				break;
+			}
+
+			let (used, end) = match chunk.iter().position(|c| *c == b'\n')
+			{
+				Some(i) => (i + 1, true),
+				None => (chunk.len(), false),
+			};
+
+			let keep = used.min((MAX_GAME_SIZE + 1).saturating_sub(buf.len()));
+
+			buf.extend_from_slice(& chunk[..keep]);
+			self.reader.consume(used);
+			n += used;
+
+			if end
+			{
+				break;
+			}
+		}
+
+		if n == 0
+		{
+			self.done = true;
+
+			return Ok(None);
+		}
+
+		let l = (String::from_utf8_lossy(& buf).into_owned(), self.offset,
+			self.line);
+
+		self.offset += n as u64;
+		self.line += 1;
+
+		Ok(Some(l))
+	}
+
+	/* Read the text of the next game, along with its byte offset and line
+	 * number. A game ends where the tags of the next game begin, at a line
+	 * that starts with a tag after the movetext, outside of a comment, or
+	 * where the movetext of a game without tags begins, after the line
+	 * with the game termination marker. The text of a game that is too
+	 * long is not kept, but it is still read up to its end. Since its
+	 * comments may have been cut off, it ends at a line that starts with a
+	 * tag after an empty line instead. */
+	fn read_game(self: & mut Self)
+		-> Result<Option<(String, u64, usize)>, PgnSkip>
+	{
+		let mut game: Option<(String, u64, usize)> = None;
+		let mut movetext = false;
+		let mut comment = false;
+		let mut ended = false;
+		let mut too_long = None;
+		let mut empty = false;
+
+		while let Some((l, offset, line)) = self.read_line()?
+		{
+			let tag = l.starts_with('[') && match too_long
+			{
+				Some(_) => empty,
+				None => !comment,
+			};
+
+			empty = l.trim().is_empty();
+
+			let next = match too_long
+			{
+				Some(_) => tag,
+				None => tag || (ended && !empty && !l.starts_with('%')),
+			};
+
+			if next && movetext
+			{
+				self.pending = Some((l, offset, line));
+
+				break;
+			}
+
+			if !tag && !l.starts_with('%')
+			{
+				let (open, end) = scan_movetext(l.as_str(), comment);
+
+				comment = open;
+				ended |= end;
+
+				if !l.trim().is_empty()
+				{
+					movetext = true;
+				}
+			}
+
+			match & mut game
+			{
+				_ if too_long.is_some() => (),
+				Some(g) if g.0.len() + l.len() > MAX_GAME_SIZE =>
+				{
+					too_long = Some((g.1, line));
+				},
+				None if l.len() > MAX_GAME_SIZE =>
+				{
+					too_long = Some((offset, line));
+				},
+				Some(g) => g.0.push_str(l.as_str()),
+				/* Skip empty lines in between games */
+				None if l.trim().is_empty() => (),
+				None => game = Some((l, offset, line)),
+			}
+		}
+
+		match too_long
+		{
+			Some((offset, line)) => Err(PgnSkip
+			{
+				offset,
+				error	: PgnError
+				{
+					line,
+					column	: 1,
+					token	: String::new(),
+					reason	: String::from("game too long"),
+				},
+			}),
+			None => Ok(game),
+		}
+	}
+}
+
+impl<R: std::io::BufRead> Iterator for PgnReader<R>
+{
+	type Item = Result<Game, PgnSkip>;
+
+	fn next(& mut self) -> Option<Self::Item>
+	{
+		let (text, offset, line) = match self.read_game()
+		{
+			Ok(g) => g?,
+			Err(e) => return Some(Err(e)),
+		};
+
+		match parse_pgn(text.as_str())
+		{
+			Ok(game) => Some(Ok(game)),
+			Err(mut error) =>
+			{
+				/* Report the line number in the whole input */
+				error.line += line - 1;
+
+				Some(Err(PgnSkip { offset, error }))
+			},
+		}
+	}
+}
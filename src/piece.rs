@@ -8,6 +8,7 @@ pub struct PieceKind
 	pub moves_fn	: fn(board: & Board, piece: & Piece, loc: Loc)
 				-> Vec<Move>,
 	pub name	: &'static str,
+	pub index	: usize,
 }
 
 #[derive(Copy, Clone)]
@@ -17,6 +18,14 @@ pub struct Piece
 	pub kind	: &'static PieceKind,
 }
 
+impl PartialEq for Piece
+{
+	fn eq(self: & Self, other: & Self) -> bool
+	{
+		self.is(other.player, other.kind)
+	}
+}
+
 impl Piece
 {
 	pub fn is_player(self: & Self, player: Player) -> bool
@@ -44,34 +53,40 @@ pub static KING: PieceKind = PieceKind
 {
 	moves_fn	: king_moves,
 	name		: "K",
+	index		: 0,
 };
 
 pub static QUEEN: PieceKind = PieceKind
 {
 	moves_fn	: queen_moves,
 	name		: "Q",
+	index		: 1,
 };
 
 pub static ROOK: PieceKind = PieceKind
 {
 	moves_fn	: cardinal_moves,
 	name		: "R",
+	index		: 2,
 };
 
 pub static BISHOP: PieceKind = PieceKind
 {
 	moves_fn	: diagonal_moves,
 	name		: "B",
+	index		: 3,
 };
 
 pub static KNIGHT: PieceKind = PieceKind
 {
 	moves_fn	: knight_moves,
 	name		: "N",
+	index		: 4,
 };
 
 pub static PAWN: PieceKind = PieceKind
 {
 	moves_fn	: pawn_moves,
 	name		: "P",
+	index		: 5,
 };
@@ -1,7 +1,24 @@
 #[cfg(test)]
 mod tests
 {
-	use crate::board::Board;
+	use crate::bitboard::{diagonal_attacks, cardinal_attacks};
+	use crate::board::{Board, Castling, Loc, Square};
+	use crate::epd::{Epd, EpdError, EpdOperation};
+	use crate::fen::FenField;
+	use crate::game::{Game, State};
+	use crate::r#move::Move;
+	use crate::move_notation::{SanError, UciError};
+	use crate::move_picker::{MoveStage, MovePicker, static_exchange};
+	use crate::packed_move::PackedMove;
+	use crate::perft::{PerftTable, PerftStats};
+	use crate::pgn::PgnReader;
+	use crate::piece::{Piece, KING, QUEEN, ROOK, BISHOP, KNIGHT, PAWN};
+	use crate::player::Player;
+	use crate::polyglot::{POLYGLOT_KEY_COUNT, POLYGLOT_RANDOM, PolyglotKeys,
+		BookEntry, PolyglotBook, BookBuilder, decode_book_move};
+	use crate::validate::PositionError;
+	use rand::{Rng, SeedableRng};
+	use std::time::Duration;
 
 	fn perft(board: & Board, depth: u32) -> u64
 	{
@@ -11,10 +28,15 @@ mod tests
 			_ =>
 			{
 				let mut n: u64 = 0;
+				let mut b = *board;
 
-				for m in & board.moves(board.player)
+				for m in & board.moves(board.player())
 				{
-					n = n + perft(& m.board, depth - 1);
+					let undo = b.make_move(m);
+
+					n = n + perft(& b, depth - 1);
+
+					b.unmake_move(m, & undo);
 				}
 
 				n
@@ -76,6 +98,42 @@ mod tests
 		assert_eq!(perft(& Board::from_fen(P2).unwrap(), 4), 4085603);
 	}
 
+	#[test]
+	fn perft_stats()
+	{
+		let stats = Board::from_fen(P2).unwrap().perft_stats(3);
+
+		assert_eq!(stats, PerftStats
+		{
+			nodes: 97862,
+			captures: 17102,
+			passants: 45,
+			castles: 3162,
+			promotions: 0,
+			checks: 993,
+			mates: 1,
+		});
+
+		let stats = Board::default().perft_stats(4);
+
+		assert_eq!(stats, PerftStats
+		{
+			nodes: 197281,
+			captures: 1576,
+			passants: 0,
+			castles: 0,
+			promotions: 0,
+			checks: 469,
+			mates: 8,
+		});
+
+		let divide = Board::default().perft_divide(2);
+
+		assert_eq!(divide.len(), 20);
+		assert!(divide.iter().all(|(_, stats)| stats.nodes == 20));
+		assert_eq!(Board::default().perft_stats(0).nodes, 1);
+	}
+
 	const P3: & str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
 
 	#[test]
@@ -103,4 +161,1594 @@ mod tests
 	{
 		assert_eq!(perft(& Board::from_fen(P5).unwrap(), 3), 62379);
 	}
+
+	fn sorted(ms: & [Move]) -> Vec<PackedMove>
+	{
+		let mut ps = ms.iter().map(|m| m.pack()).collect::<Vec<PackedMove>>();
+
+		ps.sort();
+		ps
+	}
+
+	/* Count the nodes with the captures, quiet moves and evasions, and
+	 * check that they match all legal moves on every node */
+	fn perft_split(board: & Board, depth: u32) -> u64
+	{
+		if depth == 0
+		{
+			return 1;
+		}
+
+		let player = board.player();
+		let moves = board.moves(player);
+		let evasions = board.evasions(player);
+
+		let ms = if board.is_check(player)
+		{
+			assert_eq!(sorted(& evasions), sorted(& moves));
+
+			evasions
+		}
+		else
+		{
+			assert!(evasions.is_empty());
+
+			let mut ms = board.captures(player);
+
+			assert!(ms.iter().all(|m|
+				m.is_capture() || m.is_promotion().is_some()));
+
+			ms.extend(board.quiets(player));
+
+			assert_eq!(sorted(& ms), sorted(& moves));
+
+			ms
+		};
+
+		ms.iter().map(|m| perft_split(& board.after_move(m), depth - 1))
+			.sum()
+	}
+
+	#[test]
+	fn perft_split_generators()
+	{
+		assert_eq!(perft_split(& Board::from_fen(P2).unwrap(), 3), 97862);
+		assert_eq!(perft_split(& Board::from_fen(P3).unwrap(), 4), 43238);
+		assert_eq!(perft_split(& Board::from_fen(P4).unwrap(), 3), 9467);
+		assert_eq!(perft_split(& Board::from_fen(P5).unwrap(), 3), 62379);
+	}
+
+	#[test]
+	fn parallel_perft()
+	{
+		let table = PerftTable::new(1 << 12);
+
+		for (fen, depth, nodes) in [
+			(P2, 3, 97862),
+			(P3, 4, 43238),
+			(P4, 3, 9467),
+			(P5, 3, 62379),
+		]
+		{
+			let b = Board::from_fen(fen).unwrap();
+
+			assert_eq!(b.perft(depth), nodes);
+			assert_eq!(b.parallel_perft(depth, 4, None), nodes);
+
+			/* Twice, the second time mostly from the table */
+			for _ in 0..2
+			{
+				assert_eq!(b.parallel_perft(depth, 3, Some(& table)),
+					nodes);
+			}
+		}
+
+		let b = Board::default();
+
+		assert_eq!(b.parallel_perft(0, 4, None), 1);
+		assert_eq!(b.parallel_perft(1, 4, None), 20);
+		assert_eq!(b.parallel_perft(4, 1, Some(& PerftTable::new(0))),
+			197281);
+	}
+
+	/* Play a move given by its departure and destination squares */
+	fn play(game: & mut Game, from: & str, to: & str)
+	{
+		let ms = game.get_moves(Loc::parse(from), Loc::parse(to));
+
+		assert_eq!(ms.len(), 1);
+
+		game.play_move(& ms[0]);
+	}
+
+	/* Shuffle the knights back and forth n times */
+	fn shuffle_knights(game: & mut Game, n: i32)
+	{
+		for _ in 0..n
+		{
+			play(game, "g1", "f3");
+			play(game, "g8", "f6");
+			play(game, "f3", "g1");
+			play(game, "f6", "g8");
+		}
+	}
+
+	#[test]
+	fn threefold_repetition()
+	{
+		let mut g = Game::new();
+
+		shuffle_knights(& mut g, 1);
+		assert_eq!(g.repetitions(), 2);
+		assert!(!g.claim_draw());
+
+		shuffle_knights(& mut g, 1);
+		assert_eq!(g.repetitions(), 3);
+		assert!(g.state() == State::Playing);
+		assert!(g.claim_draw());
+		assert!(g.state() == State::ThreefoldRepetition);
+		assert_eq!(g.score(), [1, 1]);
+		assert!(g.movetext().ends_with(" 1/2-1/2"));
+	}
+
+	#[test]
+	fn fivefold_repetition()
+	{
+		let mut g = Game::new();
+
+		shuffle_knights(& mut g, 4);
+		assert_eq!(g.repetitions(), 5);
+		assert!(g.state() == State::FivefoldRepetition);
+		assert_eq!(g.score(), [1, 1]);
+	}
+
+	#[test]
+	fn repetition_passant()
+	{
+		/* The passant square after 1. e4 can not be captured on, so
+		 * the position is the same as the one reached after the
+		 * knights return. */
+		let mut g = Game::new();
+
+		play(& mut g, "e2", "e4");
+		let b = g.board();
+
+		play(& mut g, "g8", "f6");
+		play(& mut g, "g1", "f3");
+		play(& mut g, "f6", "g8");
+		play(& mut g, "f3", "g1");
+		assert!(g.board().is_same_position(& b));
+
+		/* After 1. e4 d5 2. e5 f5, passant is possible */
+		let mut g = Game::new();
+
+		play(& mut g, "e2", "e4");
+		play(& mut g, "d7", "d5");
+		play(& mut g, "e4", "e5");
+		play(& mut g, "f7", "f5");
+		let b = g.board();
+
+		shuffle_knights(& mut g, 1);
+		assert!(!g.board().is_same_position(& b));
+		assert_eq!(g.repetitions(), 1);
+
+		/* The pawn on e5 stands ready to capture on d6, but is pinned */
+		let b = Board::from_fen("k3r3/3p4/8/4P3/8/8/8/4K1N1 b - - 0 1");
+		let mut g = Game::from_board(b.unwrap());
+
+		play(& mut g, "d7", "d5");
+		let b = g.board();
+
+		for _ in 0..2
+		{
+			play(& mut g, "g1", "f3");
+			play(& mut g, "a8", "a7");
+			play(& mut g, "f3", "g1");
+			play(& mut g, "a7", "a8");
+		}
+
+		assert!(g.board().is_same_position(& b));
+		assert_eq!(g.repetitions(), 3);
+		assert!(g.can_claim_draw());
+	}
+
+	#[test]
+	fn fen_clocks()
+	{
+		assert_eq!(Board::from_fen(P5).unwrap().fen(), P5);
+
+		let mut g = Game::new();
+
+		play(& mut g, "e2", "e4");
+		play(& mut g, "e7", "e5");
+		play(& mut g, "g1", "f3");
+		assert_eq!(g.board().fen(), concat!(
+			"rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R",
+			" b KQkq - 1 2"));
+
+		play(& mut g, "b8", "c6");
+		play(& mut g, "f3", "e5");
+		assert_eq!(g.board().halfmove_clock, 0);
+		assert_eq!(g.fullmove(), 3);
+	}
+
+	#[test]
+	fn fifty_move_rule()
+	{
+		let b = Board::from_fen("7k/8/8/8/8/8/8/R6K w - - 98 80");
+		let mut g = Game::from_board(b.unwrap());
+
+		play(& mut g, "a1", "a2");
+		assert!(!g.claim_draw());
+
+		play(& mut g, "h8", "g8");
+		assert!(g.state() == State::Playing);
+		assert!(g.claim_draw());
+		assert!(g.state() == State::FiftyMoveRule);
+		assert_eq!(g.score(), [1, 1]);
+	}
+
+	#[test]
+	fn seventy_five_move_rule()
+	{
+		let b = Board::from_fen("7k/8/8/8/8/8/8/R6K w - - 149 100");
+		let mut g = Game::from_board(b.unwrap());
+
+		assert!(g.state() == State::Playing);
+
+		play(& mut g, "a1", "a2");
+		assert!(g.state() == State::SeventyFiveMoveRule);
+		assert_eq!(g.score(), [1, 1]);
+	}
+
+	fn insufficient(fen: & str) -> [bool; 2]
+	{
+		let b = Board::from_fen(fen).unwrap();
+
+		[
+			b.is_insufficient_material(Player::White),
+			b.is_insufficient_material(Player::Black),
+		]
+	}
+
+	#[test]
+	fn insufficient_material()
+	{
+		assert_eq!(insufficient("8/8/4k3/8/8/3K4/8/8 w - - 0 1"),
+			[true, true]);
+		assert_eq!(insufficient("8/8/4k3/8/8/3K4/8/6N1 w - - 0 1"),
+			[true, true]);
+		assert_eq!(insufficient("8/8/4k3/8/8/3K4/8/5NN1 w - - 0 1"),
+			[false, true]);
+		assert_eq!(insufficient("8/8/4k3/8/8/3K4/8/6R1 w - - 0 1"),
+			[false, true]);
+		assert_eq!(insufficient("8/8/4kb2/8/8/3K4/8/2B5 w - - 0 1"),
+			[true, true]);
+		assert_eq!(insufficient("8/8/4kb2/8/8/3K4/8/5B2 w - - 0 1"),
+			[false, false]);
+		assert_eq!(insufficient("8/8/4kn2/8/8/3K4/8/6N1 w - - 0 1"),
+			[false, false]);
+		assert_eq!(insufficient("8/8/4kp2/8/8/3K4/8/6N1 w - - 0 1"),
+			[false, false]);
+		assert_eq!(insufficient("8/8/4kp2/8/8/3K4/8/8 w - - 0 1"),
+			[true, false]);
+	}
+
+	#[test]
+	fn dead_pawn_structure()
+	{
+		assert_eq!(insufficient(
+			"8/8/4k3/p1p1p1p1/P1P1P1P1/8/4K3/8 w - - 0 1"),
+			[true, true]);
+		assert_eq!(insufficient(
+			"8/8/4k3/p1p1p3/P1P1P3/8/4K3/8 w - - 0 1"),
+			[false, false]);
+		assert_eq!(insufficient(
+			"8/8/4k3/p1p1p1p1/P1P1P1P1/8/4K3/7B w - - 0 1"),
+			[false, false]);
+
+		let b = Board::from_fen(
+			"8/8/4k3/p1p1p1p1/P1P1P1P1/8/4K3/8 w - - 0 1").unwrap();
+		assert!(Game::from_board(b).state() == State::InsufficientMaterial);
+	}
+
+	#[test]
+	fn resignation()
+	{
+		let mut g = Game::new();
+
+		play(& mut g, "e2", "e4");
+		assert!(g.resign(Player::Black));
+		assert!(g.state() == State::Resignation(Player::Black));
+		assert_eq!(g.score(), [2, 0]);
+		assert_eq!(g.movetext(), "1. e4 1-0");
+
+		/* The game has already ended */
+		assert!(!g.resign(Player::White));
+		assert!(!g.offer_draw(Player::White));
+	}
+
+	#[test]
+	fn draw_offer()
+	{
+		let mut g = Game::new();
+
+		/* White offers a draw after making a move */
+		play(& mut g, "e2", "e4");
+		assert!(g.offer_draw(Player::White));
+		assert!(g.draw_offer() == Some((Player::White, 1)));
+
+		/* The offering player can not accept or decline */
+		assert!(!g.accept_draw(Player::White));
+		assert!(!g.decline_draw(Player::White));
+
+		/* The offer lapses when black moves */
+		play(& mut g, "e7", "e5");
+		assert!(g.draw_offer().is_none());
+		assert!(!g.accept_draw(Player::Black));
+
+		/* White offers a draw before making a move */
+		assert!(g.offer_draw(Player::White));
+		play(& mut g, "g1", "f3");
+		assert!(g.draw_offer() == Some((Player::White, 2)));
+		assert!(g.decline_draw(Player::Black));
+		assert!(g.draw_offer().is_none());
+
+		assert!(g.offer_draw(Player::Black));
+		assert!(g.accept_draw(Player::White));
+		assert!(g.state() == State::DrawAgreement);
+		assert_eq!(g.score(), [1, 1]);
+		assert_eq!(g.movetext(), "1. e4 e5 2. Nf3 1/2-1/2");
+	}
+
+	#[test]
+	fn adjudication()
+	{
+		let mut g = Game::new();
+
+		assert!(g.adjudicate(Some(Player::Black)));
+		assert!(g.state() == State::Adjudication(Some(Player::Black)));
+		assert_eq!(g.score(), [0, 2]);
+
+		let mut g = Game::new();
+
+		assert!(g.adjudicate(None));
+		assert_eq!(g.score(), [1, 1]);
+	}
+
+	#[test]
+	fn pgn_export()
+	{
+		let mut g = Game::new();
+
+		g.set_tag("White", "Doe, \"John\"");
+		g.set_tag("Annotator", "Nobody");
+
+		play(& mut g, "e2", "e4");
+		g.annotation_mut(1).clock = Some(Duration::from_secs(5399));
+		play(& mut g, "e7", "e5");
+		g.annotation_mut(2).nags.push(2);
+		g.annotation_mut(2).comment = Some(String::from("Risky {!}"));
+		play(& mut g, "g1", "f3");
+		g.resign(Player::Black);
+
+		assert_eq!(g.to_pgn(), concat!(
+			"[Event \"?\"]\n",
+			"[Site \"?\"]\n",
+			"[Date \"????.??.??\"]\n",
+			"[Round \"?\"]\n",
+			"[White \"Doe, \\\"John\\\"\"]\n",
+			"[Black \"?\"]\n",
+			"[Result \"1-0\"]\n",
+			"[Annotator \"Nobody\"]\n",
+			"\n",
+			"1. e4 {[%clk 1:29:59]} 1... e5 $2 {Risky {!)} 2. Nf3 1-0\n"));
+	}
+
+	#[test]
+	fn pgn_export_setup()
+	{
+		let b = Board::from_fen(P5).unwrap();
+		let mut g = Game::from_board(b);
+
+		let ms = g.get_moves(Loc::parse("d7"), Loc::parse("c8"));
+		g.play_move(& ms[0]);
+		play(& mut g, "d8", "c8");
+
+		let pgn = g.to_pgn();
+
+		assert!(pgn.contains(
+			format!("[SetUp \"1\"]\n[FEN \"{}\"]\n", P5).as_str()));
+		assert!(pgn.ends_with("\n8. dxc8=Q Qxc8 *\n"));
+
+		let pgn = Game::from_board(Board::default()).to_pgn();
+
+		assert!(!pgn.contains("[SetUp") && !pgn.contains("[FEN"));
+
+		/* Black moves first, and lines are wrapped */
+		let b = Board::from_fen(concat!(
+			"rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR",
+			" b KQkq - 0 1")).unwrap();
+		let mut g = Game::from_board(b);
+
+		play(& mut g, "e7", "e5");
+		shuffle_knights(& mut g, 4);
+		assert!(g.movetext().starts_with("1... e5 2. Nf3 Nf6 3. Ng1"));
+
+		let pgn = g.to_pgn();
+		let movetext = pgn.split("\n\n").nth(1).unwrap();
+
+		assert!(movetext.lines().count() > 1);
+		assert!(movetext.lines().all(|l| l.len() <= 79));
+	}
+
+	#[test]
+	fn pgn_import()
+	{
+		let pgn = concat!(
+			"[Event \"Casual \\\"game\\\"\"]\n",
+			"[Site \"?\"]\n",
+			"[Result \"0-1\"]\n",
+			"\n",
+			"% escaped line\n",
+			"{Start} 1.e4 {[%clk 0:05:00] Best by test} e5 $1 2. Nf3!? ",
+			"(2. f4 exf4 (2... d5) 3. Nf3) ; rest of line\n",
+			"2... Nc6 3. Bb5 a6 4. 0-0 0-1\n");
+
+		let g = Game::from_pgn(pgn).unwrap();
+
+		assert_eq!(g.halfmove(), 7);
+		assert_eq!(g.tag("Event"), Some("Casual \"game\""));
+		assert!(g.state() == State::Resignation(Player::White));
+		assert_eq!(g.annotation(0).comment.as_deref(), Some("Start"));
+		assert_eq!(g.annotation(1).clock, Some(Duration::from_secs(300)));
+		assert_eq!(g.annotation(1).comment.as_deref(),
+			Some("Best by test"));
+		assert_eq!(g.annotation(2).nags, vec![1]);
+		assert_eq!(g.annotation(3).nags, vec![5]);
+		assert_eq!(g.movetext(),
+			"1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. O-O 0-1");
+
+		/* Exporting and importing a game gives back the same game */
+		let h = Game::from_pgn(g.to_pgn().as_str()).unwrap();
+
+		assert_eq!(h.to_pgn(), g.to_pgn());
+	}
+
+	#[test]
+	fn pgn_import_setup()
+	{
+		let pgn = format!("[FEN \"{}\"]\n\n8. dxc8=Q Qxc8 9. O-O *", P5);
+		let g = Game::from_pgn(pgn.as_str()).unwrap();
+
+		assert!(g.is_setup());
+		assert_eq!(g.movetext(), "8. dxc8=Q Qxc8 9. O-O");
+		assert!(g.state() == State::Playing);
+	}
+
+	#[test]
+	fn pgn_import_errors()
+	{
+		let e = Game::from_pgn("1. e4 e5\n2. Nf3 Nf3 *").err().unwrap();
+
+		assert_eq!((e.line, e.column), (2, 8));
+		assert_eq!(e.token, "Nf3");
+		assert_eq!(e.reason, "illegal move");
+
+		let e = Game::from_pgn("1. e4 (1. d4 d5 (1... Nf6 2. Nf7)) *")
+			.err().unwrap();
+
+		assert_eq!((e.line, e.column), (1, 30));
+		assert_eq!(e.token, "Nf7");
+
+		let e = Game::from_pgn("[Event \"x\"\n1. e4 *").err().unwrap();
+
+		assert_eq!((e.line, e.column), (2, 1));
+		assert_eq!(e.reason, "expected `]`");
+
+		let e = Game::from_pgn("1. f3 e5 2. g4 Qh4# 1-0").err().unwrap();
+
+		assert_eq!(e.token, "1-0");
+
+		let e = Game::from_pgn("1. e4 {oops").err().unwrap();
+
+		assert_eq!((e.line, e.column), (1, 7));
+		assert_eq!(e.reason, "unterminated comment");
+	}
+
+	#[test]
+	fn pgn_reader()
+	{
+		let pgn = concat!(
+			"[Event \"One\"]\n",
+			"\n",
+			"1. e4 e5 *\n",
+			"\n",
+			"[Event \"Two\"]\n",
+			"\n",
+			"1. e4 e4 *\n",
+			"[Site \"Lost\"]\n",
+			"\n",
+			"1. d4 *\n",
+			"\n",
+			"[Event \"Three\"]\n",
+			"[Result \"1-0\"]\n",
+			"\n",
+			"1. d4 d5\n",
+			"2. c4 1-0\n");
+
+		let rs = PgnReader::new(pgn.as_bytes()).collect::<Vec<_>>();
+
+		assert_eq!(rs.len(), 4);
+
+		let g = rs[0].as_ref().ok().unwrap();
+		assert_eq!(g.tag("Event"), Some("One"));
+		assert_eq!(g.movetext(), "1. e4 e5");
+
+		let e = rs[1].as_ref().err().unwrap();
+		assert_eq!(e.offset, 27);
+		assert_eq!((e.error.line, e.error.column), (7, 7));
+		assert_eq!(e.error.token, "e4");
+
+		/* A game without an Event tag is not lost after an error */
+		let g = rs[2].as_ref().ok().unwrap();
+		assert_eq!(g.tag("Site"), Some("Lost"));
+		assert_eq!(g.movetext(), "1. d4");
+
+		let g = rs[3].as_ref().ok().unwrap();
+		assert_eq!(g.tag("Event"), Some("Three"));
+		assert_eq!(g.movetext(), "1. d4 d5 2. c4 1-0");
+
+		/* A line that starts with a bracket inside a comment does not
+		 * start a new game */
+		let pgn = "1. e4 {a comment\n[not a tag]\nends} e5 *\n";
+		let rs = PgnReader::new(pgn.as_bytes()).collect::<Vec<_>>();
+
+		assert_eq!(rs.len(), 1);
+		assert_eq!(rs[0].as_ref().ok().unwrap().annotation(1).comment
+			.as_deref(), Some("a comment [not a tag] ends"));
+
+		/* Games without tags start after the termination marker of the
+		 * previous game, outside of comments */
+		let pgn = concat!(
+			"[Event \"Tags\"]\n",
+			"\n",
+			"1. e4 e5 {not the end: 1-0} 1-0\n",
+			"1. d4 d5 0-1\n",
+			"\n",
+			"1. c4 ; *\n",
+			"1... c5 *\n");
+		let rs = PgnReader::new(pgn.as_bytes()).collect::<Vec<_>>();
+
+		assert_eq!(rs.len(), 3);
+		assert_eq!(rs.iter().map(|r| r.as_ref().ok().unwrap().movetext())
+				.collect::<Vec<String>>(),
+			["1. e4 e5 1-0", "1. d4 d5 0-1", "1. c4 c5"]);
+
+		let g = rs[0].as_ref().ok().unwrap();
+		assert_eq!(g.tag("Event"), Some("Tags"));
+		assert_eq!(g.annotation(2).comment.as_deref(),
+			Some("not the end: 1-0"));
+
+		/* A line too long to be kept is skipped to its end */
+		let long = format!("1. e4 {{{}}} e5 *\n\n[Event \"Next\"]\n\n1. d4 *",
+			"x".repeat(3 << 20));
+		let rs = PgnReader::new(long.as_bytes()).collect::<Vec<_>>();
+
+		assert_eq!(rs.len(), 2);
+		assert_eq!(rs[0].as_ref().err().unwrap().error.reason,
+			"game too long");
+		assert_eq!(rs[1].as_ref().ok().unwrap().tag("Event"), Some("Next"));
+	}
+
+	fn san(fen: & str, san: & str) -> Result<String, SanError>
+	{
+		let b = Board::from_fen(fen).unwrap();
+		let m = b.parse_san(san)?;
+
+		Ok(format!("{}{}{}", m.from.notation(true, true),
+			m.to.notation(true, true),
+			m.is_promotion().map_or("", |k| k.name)))
+	}
+
+	#[test]
+	fn san_parsing()
+	{
+		const N: & str =
+			"rnbqkbnr/pppppppp/8/8/R7/5N2/PPP1PPPP/RNBQKB2 w Qkq - 0 1";
+		const R: & str = "4k3/8/8/8/R7/8/8/R3K3 w - - 0 1";
+
+		assert_eq!(san(N, "Nd2"), Err(SanError::Ambiguous));
+		assert_eq!(san(N, "Nbd2"), Ok(String::from("b1d2")));
+		assert_eq!(san(N, "Nfd2"), Ok(String::from("f3d2")));
+		assert_eq!(san(N, "Nf3d2"), Ok(String::from("f3d2")));
+		assert_eq!(san(R, "Ra3"), Err(SanError::Ambiguous));
+		assert_eq!(san(R, "R4a3"), Ok(String::from("a4a3")));
+		assert_eq!(san(R, "R1a3"), Ok(String::from("a1a3")));
+		assert_eq!(san(N, "Rxa7"), Ok(String::from("a4a7")));
+		assert_eq!(san(N, "Nd4!?"), Ok(String::from("f3d4")));
+		assert_eq!(san(N, "Nd5"), Err(SanError::Illegal));
+		assert_eq!(san(N, "d4"), Err(SanError::Illegal));
+		assert_eq!(san(N, "Xd4"), Err(SanError::Unknown));
+		assert_eq!(san(N, "Nd9"), Err(SanError::Unknown));
+		assert_eq!(san(N, ""), Err(SanError::Unknown));
+
+		const P: & str = "2n1k3/3P4/8/3pP3/8/8/8/4K3 w - d6 0 1";
+
+		assert_eq!(san(P, "exd6"), Ok(String::from("e5d6")));
+		assert_eq!(san(P, "ed6"), Ok(String::from("e5d6")));
+		assert_eq!(san(P, "d8=Q+"), Ok(String::from("d7d8Q")));
+		assert_eq!(san(P, "dxc8N"), Ok(String::from("d7c8N")));
+		assert_eq!(san(P, "d8"), Err(SanError::Illegal));
+		assert_eq!(san(P, "d8=K"), Err(SanError::Unknown));
+
+		assert_eq!(san(P2, "O-O"), Ok(String::from("e1g1")));
+		assert_eq!(san(P2, "0-0-0"), Ok(String::from("e1c1")));
+		assert_eq!(san(N, "O-O"), Err(SanError::Illegal));
+
+		let mut g = Game::new();
+
+		for m in ["e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "O-O"]
+		{
+			g.play_san(m).unwrap();
+		}
+
+		assert_eq!(g.movetext(), "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. O-O");
+		assert_eq!(g.play_san("Kf7").err(), Some(SanError::Illegal));
+	}
+
+	#[test]
+	fn uci_notation()
+	{
+		let b = Board::from_fen(P2).unwrap();
+
+		for (uci, san) in
+			[
+				("e1g1", "O-O"), ("e1h1", "O-O"),
+				("e1c1", "O-O-O"), ("e1a1", "O-O-O"),
+				("e5f7", "Nxf7"), ("d5e6", "dxe6"),
+			]
+		{
+			let m = b.parse_uci(uci).unwrap();
+			let n = b.parse_san(san).unwrap();
+
+			assert_eq!(m.uci(), n.uci());
+			assert_eq!(m.uci_chess960(), n.uci_chess960());
+		}
+
+		assert_eq!(b.parse_san("O-O").unwrap().uci(), "e1g1");
+		assert_eq!(b.parse_san("O-O").unwrap().uci_chess960(), "e1h1");
+		assert_eq!(b.parse_san("O-O-O").unwrap().uci_chess960(), "e1a1");
+		assert_eq!(b.parse_san("Nxf7").unwrap().uci_chess960(), "e5f7");
+		assert_eq!(b.parse_uci("e1f2").err(), Some(UciError::Illegal));
+
+		let b = Board::from_fen("2n1k3/3P4/8/3pP3/8/8/8/4K3 w - d6 0 1")
+			.unwrap();
+
+		assert_eq!(b.parse_uci("d7d8q").unwrap().uci(), "d7d8q");
+		assert_eq!(b.parse_uci("d7c8n").unwrap().uci(), "d7c8n");
+		assert!(b.parse_uci("e5d6").unwrap().is_passant());
+		assert_eq!(b.parse_uci("d7d8").err(), Some(UciError::Illegal));
+		assert_eq!(b.parse_uci("e5e6q").err(), Some(UciError::Illegal));
+		assert_eq!(b.parse_uci("d7d8k").err(), Some(UciError::Unknown));
+		assert_eq!(b.parse_uci("d7").err(), Some(UciError::Unknown));
+		assert_eq!(b.parse_uci("d7d9").err(), Some(UciError::Unknown));
+	}
+
+	fn fen_error(fen: & str) -> (FenField, usize, String)
+	{
+		let e = Board::from_fen(fen).err().unwrap();
+
+		(e.field, e.offset, e.reason)
+	}
+
+	#[test]
+	fn fen_errors()
+	{
+		assert_eq!(fen_error("8/8/8/8/8/8/8/8 w - - 0 1 x"),
+			(FenField::FullmoveNumber, 25,
+			String::from("unexpected text after fullmove number")));
+		assert_eq!(fen_error("4k3/8/8/8/8/3P5/8/4K3 w - - 0 1"),
+			(FenField::Placement, 12,
+			String::from("rank 3 has 9 files")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K2 w - - 0 1"),
+			(FenField::Placement, 16,
+			String::from("rank 1 has 7 files")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/4K3 w - - 0 1"),
+			(FenField::Placement, 17,
+			String::from("fewer than 8 ranks")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K3/8 w - - 0 1"),
+			(FenField::Placement, 20,
+			String::from("more than 8 ranks")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4X3 w - - 0 1"),
+			(FenField::Placement, 17,
+			String::from("invalid piece `X`")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K3 x - - 0 1"),
+			(FenField::Player, 20,
+			String::from("expected `w` or `b`, found `x`")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K3 w KQKq - 0 1"),
+			(FenField::Castling, 24,
+			String::from("duplicate K in castling field")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K3 w KX - 0 1"),
+			(FenField::Castling, 23,
+			String::from("invalid castling right `X`")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K3 w - e9 0 1"),
+			(FenField::Passant, 24,
+			String::from("invalid square `e9`")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K3 w - -"),
+			(FenField::HalfmoveClock, 25,
+			String::from("missing halfmove clock")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K3 w - - x 1"),
+			(FenField::HalfmoveClock, 26,
+			String::from("invalid halfmove clock `x`")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K3 w - - 0 0"),
+			(FenField::FullmoveNumber, 28,
+			String::from("invalid fullmove number `0`")));
+		assert_eq!(fen_error(" 4k3/8/8/8/8/8/8/4K3  x - - 0 1 "),
+			(FenField::Player, 22,
+			String::from("expected `w` or `b`, found `x`")));
+		assert_eq!(fen_error("4k3/8/8/8/8/8/8/4K3 w\t- -  "),
+			(FenField::HalfmoveClock, 25,
+			String::from("missing halfmove clock")));
+		assert_eq!(fen_error(""),
+			(FenField::Placement, 0,
+			String::from("missing piece placement")));
+
+		/* Runs of whitespace separate fields, and the ends are trimmed */
+		let fen = "4k3/8/8/8/8/8/8/4K3 w - - 0 1";
+		assert_eq!(Board::from_fen("  4k3/8/8/8/8/8/8/4K3 w  -\t- 0 1 \n")
+			.unwrap().fen(), fen);
+		assert_eq!(Board::from_fen(& format!("{} ", fen)).unwrap().fen(),
+			fen);
+
+		let e = Board::from_fen("8/8/8/8/8/8/8/8 w - -").err().unwrap();
+		assert_eq!(e.to_string(), concat!("halfmove clock field at",
+			" character 21: missing halfmove clock"));
+	}
+
+	fn validate(fen: & str) -> Result<(), Vec<PositionError>>
+	{
+		Board::from_fen(fen).unwrap().validate()
+	}
+
+	#[test]
+	fn position_validation()
+	{
+		let loc = |s| Loc::parse(s).unwrap();
+
+		assert_eq!(Board::default().validate(), Ok(()));
+		assert_eq!(validate(P5), Ok(()));
+		assert_eq!(validate(
+			"rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3"),
+			Ok(()));
+
+		assert_eq!(validate("8/8/8/8/8/8/8/4K3 w - - 0 1"),
+			Err(vec![PositionError::KingCount(Player::Black, 0)]));
+		assert_eq!(validate("3kk3/8/8/8/8/8/8/4K3 w - - 0 1"),
+			Err(vec![PositionError::KingCount(Player::Black, 2)]));
+		assert_eq!(validate("4k2P/8/8/8/8/8/8/p3K3 w - - 0 1"),
+			Err(vec![PositionError::PawnOnBackRank(loc("a1")),
+				PositionError::PawnOnBackRank(loc("h8"))]));
+		assert_eq!(validate("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1"),
+			Err(vec![PositionError::OpponentInCheck]));
+		assert_eq!(validate("4k3/8/8/8/8/8/3K4/4k3 b - - 0 1"),
+			Err(vec![PositionError::KingCount(Player::Black, 2)]));
+		assert_eq!(validate("4k3/8/5N2/1B6/8/8/8/4R1K1 b - - 0 1"),
+			Err(vec![PositionError::TooManyCheckers(3)]));
+		assert_eq!(validate("4k3/8/8/3pP3/8/8/8/4K3 w - e6 0 1"),
+			Err(vec![PositionError::InvalidPassant(loc("e6"))]));
+		assert_eq!(validate("4k3/8/8/3pP3/8/8/8/4K3 b - d6 0 1"),
+			Err(vec![PositionError::InvalidPassant(loc("d6"))]));
+		assert_eq!(validate("4k3/8/8/8/8/8/8/R3K3 w KQ - 0 1"),
+			Err(vec![PositionError::InvalidCastling(Player::White,
+				loc("h1"))]));
+		assert_eq!(validate("r6k/8/8/8/8/8/8/4K3 w q - 0 1"),
+			Err(vec![PositionError::InvalidCastling(Player::Black,
+				loc("a8"))]));
+		assert_eq!(validate("4k3/8/8/8/8/QQQ5/PPPPPPP1/4K3 w - - 0 1"),
+			Err(vec![PositionError::PieceCount(Player::White)]));
+
+		let b = Board::from_fen("4k3/8/8/8/8/8/8/4R1K1 w - - 0 1").unwrap();
+		assert!(Game::try_from_board(b).is_err());
+		assert!(Game::try_from_board(Board::default()).is_ok());
+
+		let pgn = "[FEN \"4k3/8/8/8/8/8/8/8 w - - 0 1\"]\n\n*";
+		assert_eq!(Game::from_pgn(pgn).err().unwrap().reason,
+			"illegal position: white has 0 kings");
+	}
+
+	/* Check that the bitboards of a board agree with its squares */
+	fn check_bitboards(b: & Board)
+	{
+		for player in [Player::White, Player::Black]
+		{
+			let mut occupancy = 0;
+
+			for kind in
+				[& KING, & QUEEN, & ROOK, & BISHOP, & KNIGHT, & PAWN]
+			{
+				let mut bb = 0;
+
+				for loc in b.locations()
+				{
+					if matches!(b.at(loc), Square::Occupied(p)
+							if p.is(player, kind))
+					{
+						bb |= 1 << (loc.y * 8 + loc.x);
+					}
+				}
+
+				assert_eq!(b.bitboard(player, kind), bb);
+
+				occupancy |= bb;
+			}
+
+			assert_eq!(b.occupancy(player), occupancy);
+		}
+	}
+
+	#[test]
+	fn bitboards()
+	{
+		check_bitboards(& Board::empty());
+		check_bitboards(& Board::default());
+
+		/* Castling, passant and promotions */
+		for fen in [P2, P4, P5]
+		{
+			let b = Board::from_fen(fen).unwrap();
+
+			check_bitboards(& b);
+
+			for m in b.moves(b.player())
+			{
+				let a = b.after_move(& m);
+
+				check_bitboards(& a);
+
+				for n in a.moves(a.player())
+				{
+					check_bitboards(& a.after_move(& n));
+				}
+			}
+		}
+
+		let mut b = Board::empty();
+		b.set(Loc { x: 4, y: 0 }, Square::Occupied(Piece
+		{
+			player	: Player::White,
+			kind	: & KING,
+		}));
+		b.set(Loc { x: 4, y: 7 }, Square::Occupied(Piece
+		{
+			player	: Player::Black,
+			kind	: & ROOK,
+		}));
+
+		check_bitboards(& b);
+		assert!(b.is_check(Player::White));
+
+		b.set(Loc { x: 4, y: 7 }, Square::Empty);
+
+		check_bitboards(& b);
+		assert!(!b.is_check(Player::White));
+	}
+
+	fn naive_attacks(loc: Loc, occupied: u64, dirs: & [(i32, i32)]) -> u64
+	{
+		let mut bb = 0;
+
+		for dir in dirs
+		{
+			let mut to = loc.offset(*dir);
+
+			while to.valid()
+			{
+				bb |= 1 << (to.y * 8 + to.x);
+
+				if occupied & (1 << (to.y * 8 + to.x)) != 0
+				{
+					break;
+				}
+
+				to = to.offset(*dir);
+			}
+		}
+
+		bb
+	}
+
+	#[test]
+	fn slider_attacks()
+	{
+		let diagonal = [(-1,  1), (-1, -1), ( 1,  1), ( 1, -1)];
+		let cardinal = [(-1,  0), ( 0,  1), ( 0, -1), ( 1,  0)];
+
+		let mut rng = rand::rngs::StdRng::seed_from_u64(13);
+
+		for i in 0..1000
+		{
+			/* Alternate between boards with many and few pieces */
+			let mut occupied = rng.gen::<u64>() & rng.gen::<u64>();
+
+			if i % 2 == 0
+			{
+				occupied &= rng.gen::<u64>();
+			}
+
+			for loc in Board::empty().locations()
+			{
+				assert_eq!(diagonal_attacks(loc, occupied),
+					naive_attacks(loc, occupied, & diagonal));
+				assert_eq!(cardinal_attacks(loc, occupied),
+					naive_attacks(loc, occupied, & cardinal));
+			}
+		}
+	}
+
+	#[test]
+	fn make_unmake()
+	{
+		for fen in [P2, P3, P4, P5]
+		{
+			let mut b = Board::from_fen(fen).unwrap();
+
+			for m in b.moves(b.player())
+			{
+				let undo = b.make_move(& m);
+
+				for n in b.moves(b.player())
+				{
+					let fen = b.fen();
+					let undo = b.make_move(& n);
+
+					b.unmake_move(& n, & undo);
+
+					assert_eq!(b.fen(), fen);
+					check_bitboards(& b);
+				}
+
+				b.unmake_move(& m, & undo);
+
+				assert_eq!(b.fen(), fen);
+				check_bitboards(& b);
+			}
+		}
+
+		/* The resulting board of each kind of special move */
+		let b = Board::from_fen(P2).unwrap();
+		let a = b.after_move(& b.parse_san("O-O-O").unwrap());
+		assert_eq!(a.fen(), concat!(
+			"r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/2KR3R",
+			" b kq - 1 1"));
+
+		let b = a.after_move(& a.parse_san("c5").unwrap());
+		assert_eq!(b.passant(), Loc::parse("c6"));
+		let a = b.after_move(& b.parse_san("dxc6").unwrap());
+		assert!(a.at(Loc::parse("c5").unwrap()).empty());
+		assert_eq!(a.halfmove_clock, 0);
+		assert_eq!(a.fullmove, 2);
+
+		let b = Board::from_fen(P5).unwrap();
+		let a = b.after_move(& b.parse_san("dxc8=N").unwrap());
+		assert!(matches!(a.at(Loc::parse("c8").unwrap()),
+			Square::Occupied(p) if p.is(Player::White, & KNIGHT)));
+		assert_eq!(a.halfmove_clock, 0);
+
+		/* Moves are small, the piece is looked up on the board */
+		assert_eq!(std::mem::size_of::<Move>(), 32);
+
+		let m = b.parse_san("O-O").unwrap();
+		assert!(m.piece(& b).is(Player::White, & KING));
+		assert_eq!(m.rook(), Loc::parse("h1"));
+		assert!(b.parse_san("a4").unwrap().flags.double_push);
+	}
+
+	#[test]
+	fn packed_moves()
+	{
+		let mut packed = std::collections::HashSet::<PackedMove>::new();
+
+		for fen in [P2, P3, P4, P5]
+		{
+			let b = Board::from_fen(fen).unwrap();
+
+			for m in b.moves(b.player())
+			{
+				let p = m.pack();
+				let n = p.unpack(& b).unwrap();
+
+				assert_eq!(PackedMove::from_bits(p.bits()), p);
+				assert_eq!(n.pack(), p);
+				assert_eq!((p.from(), p.to()), (m.from, m.to));
+				assert_eq!(n.uci_chess960(), m.uci_chess960());
+				assert_eq!(n.notation(& b, & b.moves(b.player())),
+					m.notation(& b, & b.moves(b.player())));
+				assert_eq!((n.is_capture(), n.is_passant(),
+						n.is_castle()),
+					(m.is_capture(), m.is_passant(),
+						m.is_castle()));
+
+				packed.insert(p);
+			}
+		}
+
+		let b = Board::default();
+		let e4 = b.parse_san("e4").unwrap().pack();
+
+		assert_eq!(e4.bits(), 12 | 28 << 6 | 1 << 12);
+		assert!(packed.len() > 100);
+
+		/* Moves that do not fit the board */
+		assert!(PackedMove::from_bits(28 | 36 << 6).unpack(& b).is_none());
+		assert!(PackedMove::from_bits(52 | 36 << 6).unpack(& b).is_none());
+		assert!(PackedMove::from_bits(1 | 18 << 6 | 4 << 12)
+			.unpack(& b).is_none());
+		assert!(PackedMove::from_bits(4 | 6 << 6 | 2 << 12)
+			.unpack(& b).is_some());
+		assert!(PackedMove::from_bits(4 | 6 << 6 | 2 << 12)
+			.unpack(& Board::from_fen(P3).unwrap()).is_none());
+	}
+
+	fn check_hashes(b: & mut Board, depth: u32)
+	{
+		assert_eq!(b.hash(), b.compute_hash());
+
+		if depth == 0
+		{
+			return;
+		}
+
+		let hash = b.hash();
+
+		for m in b.moves(b.player())
+		{
+			let undo = b.make_move(& m);
+
+			check_hashes(b, depth - 1);
+
+			b.unmake_move(& m, & undo);
+
+			assert_eq!(b.hash(), hash);
+		}
+	}
+
+	#[test]
+	fn zobrist_hashing()
+	{
+		for fen in [P2, P3, P4, P5]
+		{
+			check_hashes(& mut Board::from_fen(fen).unwrap(), 3);
+		}
+
+		check_hashes(& mut Board::chess960(0).unwrap(), 3);
+
+		/* The setters of the state keep the hash up to date */
+		let mut b = Board::from_fen("4k3/8/8/8/3pP3/8/8/R3K2R w KQ - 0 1")
+			.unwrap();
+
+		b.set_player(Player::Black);
+		assert_eq!(b.hash(), b.compute_hash());
+
+		b.set_passant(Loc::parse("e3"));
+		assert_eq!(b.hash(), b.compute_hash());
+
+		b.set_player(Player::White);
+		assert_eq!(b.hash(), b.compute_hash());
+
+		b.set_castling(Player::White, Castling
+		{
+			k: None,
+			q: Loc::parse("a1"),
+		});
+		assert_eq!(b.hash(), b.compute_hash());
+
+		b.set_passant(None);
+		assert_eq!(b.hash(), b.compute_hash());
+		assert_eq!(b.hash(), Board::from_fen(
+			"4k3/8/8/8/3pP3/8/8/R3K2R w Q - 0 1").unwrap().hash());
+
+		/* Transpositions have the same hash */
+		let mut g = Game::new();
+		let mut h = Game::new();
+
+		for (s, t) in [("Nf3", "Nc3"), ("Nf6", "Nc6"), ("Nc3", "Nf3"),
+				("Nc6", "Nf6")]
+		{
+			g.play_san(s).unwrap();
+			h.play_san(t).unwrap();
+		}
+
+		assert_eq!(g.board().hash(), h.board().hash());
+		assert_ne!(g.board().hash(), Board::default().hash());
+
+		/* The passant file only counts if a capture is possible */
+		let hash = |fen| Board::from_fen(fen).unwrap().hash();
+
+		assert_ne!(hash("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"),
+			hash("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1"));
+		assert_eq!(hash("4k3/8/8/8/2p1P3/8/8/4K3 b - e3 0 1"),
+			hash("4k3/8/8/8/2p1P3/8/8/4K3 b - - 0 1"));
+		assert_ne!(hash("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"),
+			hash("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+		assert_ne!(hash("4k3/8/8/8/8/8/8/4K3 w - - 0 1"),
+			hash("4k3/8/8/8/8/8/8/4K3 b - - 0 1"));
+	}
+
+	/* Keys published with the Polyglot book format */
+	#[test]
+	fn polyglot_keys()
+	{
+		let key = |fen| Board::from_fen(fen).unwrap().polyglot_key();
+
+		assert_eq!(Board::default().polyglot_key(), 0x463b96181691fc9c);
+		assert_eq!(key(concat!("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/",
+			"RNBQKBNR b KQkq e3 0 1")), 0x823c9b50fd114196);
+		assert_eq!(key(concat!("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/",
+			"RNBQKBNR w KQkq d6 0 2")), 0x0756b94461c50fb0);
+		assert_eq!(key(concat!("rnbqkbnr/ppp1pppp/8/3pP3/8/8/PPPP1PPP/",
+			"RNBQKBNR b KQkq - 0 2")), 0x662fafb965db29d4);
+		assert_eq!(key(concat!("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/",
+			"RNBQKBNR w KQkq f6 0 3")), 0x22a48b5a8e47ff78);
+		assert_eq!(key(concat!("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPPKPPP/",
+			"RNBQ1BNR b kq - 0 3")), 0x652a607ca3f242c1);
+		assert_eq!(key(concat!("rnbq1bnr/ppp1pkpp/8/3pPp2/8/8/PPPPKPPP/",
+			"RNBQ1BNR w - - 0 4")), 0x00fdd303c946bdd9);
+		assert_eq!(key(concat!("rnbqkbnr/p1pppppp/8/8/PpP4P/8/1P1PPPP1/",
+			"RNBQKBNR b KQkq c3 0 3")), 0x3c8123ea7b067637);
+		assert_eq!(key(concat!("rnbqkbnr/p1pppppp/8/8/P6P/R1p5/1P1PPPP1/",
+			"1NBQKBNR b Kkq - 0 4")), 0x5c3f9b829b279560);
+
+		let keys = PolyglotKeys::default();
+
+		assert!(keys.is_standard());
+		assert_eq!(keys.key(& Board::default()), 0x463b96181691fc9c);
+		assert!(PolyglotKeys::new(& POLYGLOT_RANDOM).unwrap().is_standard());
+	}
+
+	#[test]
+	fn polyglot_book()
+	{
+		let mut rng = rand::rngs::StdRng::seed_from_u64(17);
+		let random = (0..POLYGLOT_KEY_COUNT).map(|_| rng.gen::<u64>())
+			.collect::<Vec<u64>>();
+		let keys = PolyglotKeys::new(& random).unwrap();
+
+		assert!(PolyglotKeys::new(& random[1..]).is_none());
+		assert!(!keys.is_standard());
+
+		let bytes = random.iter().flat_map(|r| r.to_be_bytes())
+			.collect::<Vec<u8>>();
+		let read = PolyglotKeys::read(& bytes[..]).unwrap();
+		assert!(PolyglotKeys::read(& bytes[1..]).is_err());
+
+		/* The layout of the keys: white king on e1, black king on e8,
+		 * white has the turn */
+		let key = |fen| keys.key(& Board::from_fen(fen).unwrap());
+		let kings = random[708] ^ random[700];
+
+		assert_eq!(key("4k3/8/8/8/8/8/8/4K3 w - - 0 1"), kings ^ random[780]);
+		assert_eq!(key("4k3/8/8/8/8/8/8/4K3 b - - 0 1"), kings);
+		assert_eq!(key("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1")
+			^ key("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"), random[769]);
+		assert_eq!(key("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1")
+			^ key("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1"), random[776]);
+		assert_eq!(key("4k3/8/8/8/2p1P3/8/8/4K3 b - e3 0 1"),
+			key("4k3/8/8/8/2p1P3/8/8/4K3 b - - 0 1"));
+		assert_eq!(read.key(& Board::default()),
+			keys.key(& Board::default()));
+
+		/* Every move survives encoding */
+		for fen in [P2, P3, P4, P5]
+		{
+			let b = Board::from_fen(fen).unwrap();
+			let ms = b.moves(b.player());
+
+			for m in & ms
+			{
+				let n = decode_book_move(m.polyglot(), & ms).unwrap();
+
+				assert_eq!(n.uci_chess960(), m.uci_chess960());
+			}
+		}
+
+		let b = Board::from_fen(P2).unwrap();
+		assert_eq!(b.parse_san("O-O").unwrap().polyglot(), 7 | 4 << 6);
+
+		/* A book with an illegal move and a different position, out of
+		 * order */
+		let b = Board::default();
+		let k = keys.key(& b);
+		let entry = |key, m: & str, weight| BookEntry
+		{
+			key,
+			mv: Move::polyglot(& b.parse_uci(m).unwrap()),
+			weight,
+			learn: 0,
+		};
+
+		let mut illegal = entry(k, "e2e4", 9);
+		illegal.mv = 4 | 4 << 3 | 4 << 6 | 1 << 9;
+
+		let entries = [entry(k, "d2d4", 1), entry(k ^ 1, "g1f3", 9),
+			entry(k, "e2e4", 3), illegal];
+
+		let bytes = entries.iter().flat_map(|e| e.to_bytes())
+			.collect::<Vec<u8>>();
+		let book = PolyglotBook::read_with_keys(keys, & bytes[..]).unwrap();
+
+		assert_eq!(book.entries(& b).len(), 3);
+		assert_eq!(book.moves(& b).iter()
+				.map(|(m, w)| (m.uci(), *w))
+				.collect::<Vec<(String, u16)>>(),
+			[(String::from("d2d4"), 1), (String::from("e2e4"), 3)]);
+		assert_eq!(book.best_move(& b).unwrap().uci(), "e2e4");
+
+		let mut n = 0;
+
+		for _ in 0..1000
+		{
+			if book.random_move(& b, & mut rng).unwrap().uci() == "e2e4"
+			{
+				n += 1;
+			}
+		}
+
+		assert!(n > 700 && n < 800);
+
+		let a = b.after_move(& b.parse_san("e4").unwrap());
+		assert!(book.best_move(& a).is_none());
+		assert!(book.random_move(& a, & mut rng).is_none());
+
+		assert!(PolyglotBook::read_with_keys(book.keys().clone(), & bytes[1..])
+			.is_err());
+	}
+
+	#[test]
+	fn polyglot_book_builder()
+	{
+		let mut rng = rand::rngs::StdRng::seed_from_u64(19);
+		let random = (0..POLYGLOT_KEY_COUNT).map(|_| rng.gen::<u64>())
+			.collect::<Vec<u64>>();
+		let keys = PolyglotKeys::new(& random).unwrap();
+
+		let game = |moves: & [& str], winner|
+		{
+			let mut g = Game::new();
+
+			for m in moves
+			{
+				g.play_san(m).unwrap();
+			}
+
+			g.adjudicate(winner);
+			g
+		};
+
+		let games = [
+			game(& ["e4", "e5", "Nf3"], Some(Player::White)),
+			game(& ["e4", "c5", "Nf3"], None),
+			game(& ["d4", "d5"], Some(Player::Black)),
+			game(& ["e4", "c5"], Some(Player::Black)),
+		];
+
+		let mut builder = BookBuilder::with_keys(keys.clone(), 2, 1);
+		builder.add_games(games.clone());
+
+		let mut bytes = Vec::<u8>::new();
+		builder.write(& mut bytes).unwrap();
+
+		let entries = builder.entries();
+		assert_eq!(bytes.len(), entries.len() * 16);
+		assert!(entries.windows(2).all(|e| e[0].key < e[1].key
+			|| (e[0].key == e[1].key && e[0].weight >= e[1].weight)));
+
+		let book = PolyglotBook::read_with_keys(keys.clone(), & bytes[..])
+			.unwrap();
+		let moves = |b: & Board| book.moves(b).iter()
+			.map(|(m, w)| (m.uci(), *w))
+			.collect::<Vec<(String, u16)>>();
+
+		let b = Board::default();
+		assert_eq!(moves(& b), [(String::from("e2e4"), 3),
+			(String::from("d2d4"), 0)]);
+
+		let b = b.after_move(& b.parse_san("e4").unwrap());
+		assert_eq!(moves(& b), [(String::from("c7c5"), 3),
+			(String::from("e7e5"), 0)]);
+
+		/* Moves beyond the depth are left out */
+		let b = b.after_move(& b.parse_san("c5").unwrap());
+		assert!(moves(& b).is_empty());
+
+		/* Moves played fewer times than the minimum are left out */
+		let mut builder = BookBuilder::with_keys(keys.clone(), 2, 2);
+		builder.add_games(games);
+		assert_eq!(builder.entries().len(), 2);
+
+		/* Weights are scaled down to fit */
+		let mut builder = BookBuilder::with_keys(keys, 1, 1);
+		let e4 = game(& ["e4"], Some(Player::White));
+		let d4 = game(& ["d4"], None);
+
+		for i in 0..34000
+		{
+			builder.add_game(if i % 34 == 0 { & d4 } else { & e4 });
+		}
+
+		let entries = builder.entries();
+		assert_eq!((entries[0].weight, entries[1].weight), (65535, 992));
+
+		/* A book with the standard keys, which other tools can read */
+		let mut builder = BookBuilder::new(4, 1);
+		builder.add_game(& game(& ["e4", "d5", "e5", "f5"],
+			Some(Player::White)));
+
+		let entries = builder.entries();
+		let e4 = BookEntry
+		{
+			key: 0x463b96181691fc9c,
+			mv: 0x031c,
+			weight: 2,
+			learn: 0,
+		};
+
+		assert_eq!(entries.len(), 4);
+		assert!(entries.contains(& e4));
+		assert!(entries.iter().any(|e| e.key == 0x662fafb965db29d4
+			&& e.mv == 0x0d65 && e.weight == 0));
+
+		let mut bytes = Vec::<u8>::new();
+		builder.write(& mut bytes).unwrap();
+
+		let book = PolyglotBook::read(& bytes[..]).unwrap();
+		assert_eq!(book.best_move(& Board::default()).unwrap().uci(), "e2e4");
+	}
+
+	#[test]
+	fn attack_queries()
+	{
+		let bits = |locs: & [& str]| locs.iter()
+			.map(|l| Loc::parse(l).unwrap())
+			.fold(0, |bb, l| bb | 1 << (l.y * 8 + l.x));
+		let loc = |l| Loc::parse(l).unwrap();
+
+		let b = Board::from_fen("4r1k1/8/8/b7/4P2q/2Nn4/4PP2/4K3 w - - 0 1")
+			.unwrap();
+
+		assert_eq!(b.checkers(), bits(& ["d3"]));
+		let attackers = |l, by| b.attackers_to(loc(l), by)
+			.collect::<Vec<Loc>>();
+
+		assert_eq!(attackers("e1", Player::Black), [loc("d3")]);
+		assert_eq!(attackers("f2", Player::Black), [loc("d3"), loc("h4")]);
+		assert_eq!(attackers("d3", Player::White), [loc("e2")]);
+		assert_eq!(attackers("e4", Player::White), [loc("c3")]);
+		assert_eq!(b.attackers_to(loc("f2"), Player::Black).0,
+			bits(& ["d3", "h4"]));
+		assert!(b.is_square_attacked(loc("e5"), Player::Black));
+		assert!(!b.is_square_attacked(loc("e6"), Player::White));
+
+		/* The pawn on e2 is not pinned, since e4 blocks the rook too */
+		assert_eq!(b.pinned_pieces(Player::White), bits(& ["c3", "f2"]));
+		assert_eq!(b.pinned_pieces(Player::Black), 0);
+
+		for fen in [P2, P3, P4, P5]
+		{
+			let b = Board::from_fen(fen).unwrap();
+
+			for m in b.moves(b.player())
+			{
+				let a = b.after_move(& m);
+
+				assert_eq!(a.checkers() != 0, a.is_check(a.player()));
+				assert_eq!(a.checkers() != 0, m.is_check(& b));
+			}
+		}
+	}
+
+	#[test]
+	fn move_picker()
+	{
+		let see = |fen, san|
+		{
+			let b = Board::from_fen(fen).unwrap();
+
+			static_exchange(& b, & b.parse_san(san).unwrap())
+		};
+
+		assert_eq!(see("4k3/8/3p4/4p3/8/8/8/4RK2 w - - 0 1", "Rxe5"), -400);
+		assert_eq!(see("4k3/8/3p4/4p3/3P4/8/8/4K3 w - - 0 1", "dxe5"), 0);
+		assert_eq!(see("4k3/4r3/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "Rxe5"),
+			100);
+		assert_eq!(see("4k3/8/8/4p3/8/8/8/4QK2 w - - 0 1", "Qxe5"), 100);
+
+		/* The picker returns exactly the legal moves, in stages */
+		for fen in [P2, P3, P4, P5]
+		{
+			let b = Board::from_fen(fen).unwrap();
+
+			for n in b.moves(b.player())
+			{
+				let a = b.after_move(& n);
+				let mut picker = MovePicker::new(& a, None, & []);
+				let mut ms = Vec::<Move>::new();
+				let mut stage = MoveStage::HashMove;
+
+				while let Some(m) = picker.next()
+				{
+					assert!(picker.stage() as i32 >= stage as i32);
+					stage = picker.stage();
+
+					let s = static_exchange(& a, & m);
+
+					match stage
+					{
+						MoveStage::WinningCaptures => assert!(s >= 0),
+						MoveStage::LosingCaptures => assert!(s < 0),
+						_ => (),
+					}
+
+					ms.push(m);
+				}
+
+				assert_eq!(sorted(& ms), sorted(& a.moves(a.player())));
+			}
+		}
+
+		let b = Board::from_fen(P2).unwrap();
+		let pack = |san| b.parse_san(san).unwrap().pack();
+
+		/* Killers that are captures, illegal or repeated are skipped */
+		let killers = [pack("a3"), pack("Qxf6"), pack("a3"),
+			PackedMove::from_bits(0), pack("O-O")];
+		let ms = MovePicker::new(& b, Some(pack("Bxa6")), & killers)
+			.collect::<Vec<Move>>();
+
+		assert_eq!(sorted(& ms), sorted(& b.moves(b.player())));
+
+		let mut picker = MovePicker::new(& b, Some(pack("Bxa6")), & killers);
+		let stages = std::iter::from_fn(|| picker.next()
+				.map(|m| (picker.stage(), m.uci())))
+			.filter(|(s, _)| *s != MoveStage::WinningCaptures)
+			.take(3)
+			.collect::<Vec<(MoveStage, String)>>();
+
+		assert_eq!(stages, [(MoveStage::HashMove, String::from("e2a6")),
+			(MoveStage::Killers, String::from("a2a3")),
+			(MoveStage::Killers, String::from("e1g1"))]);
+	}
+
+	#[test]
+	fn epd_records()
+	{
+		/* As in perftsuite.epd, with operations that start with ';' */
+		let e = Epd::parse("4k3/8/8/8/8/8/8/4K2R w K - ;D1 15 ;D3 2630 ;D2 \
+			66").unwrap();
+
+		assert_eq!(e.board.fen(), "4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+		assert_eq!(e.perft_counts(), [(1, 15), (2, 66), (3, 2630)]);
+		assert_eq!(e.id(), None);
+
+		let e = Epd::parse("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Rd8#; \
+			am Rd7 Rc1; id \"mate; in one\"; hmvc 12; fmvn 40").unwrap();
+
+		assert_eq!(e.id(), Some("mate; in one"));
+		assert_eq!(e.operation("am"), Some(& EpdOperation
+		{
+			opcode		: String::from("am"),
+			operands	: vec![String::from("Rd7"), String::from("Rc1")],
+		}));
+		assert_eq!((e.board.halfmove_clock, e.board.fullmove), (12, 40));
+
+		let san = |san| e.board.parse_san(san).unwrap();
+
+		assert_eq!(e.solved_by(& san("Rd8")), Ok(true));
+		assert_eq!(e.solved_by(& san("Rd7")), Ok(false));
+		assert_eq!(e.solved_by(& san("h3")), Ok(false));
+		assert!(e.perft_counts().is_empty());
+
+		/* Clocks directly after the fields, and a move to avoid only */
+		let e = Epd::parse("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 3 20 am Rd7")
+			.unwrap();
+
+		assert_eq!((e.board.halfmove_clock, e.board.fullmove), (3, 20));
+		assert_eq!(e.solved_by(& san("h3")), Ok(true));
+		assert_eq!(e.solved_by(& san("Rd7")), Ok(false));
+
+		let e = Epd::parse("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - bm Qd8")
+			.unwrap();
+
+		assert_eq!(e.solved_by(& san("Rd8")), Err(SanError::Illegal));
+
+		let operation = |epd| match Epd::parse(epd)
+		{
+			Err(EpdError::Operation { offset, .. }) => Some(offset),
+			_ => None,
+		};
+
+		assert_eq!(operation("8/8/8/8/8/8/8/K1k5 w - - id \"x\"; 1a b;"),
+			Some(33));
+		assert_eq!(operation("8/8/8/8/8/8/8/K1k5 w - - id \"x;"), Some(28));
+		assert_eq!(operation("8/8/8/8/8/8/8/K1k5 w - - hmvc -1;"), Some(25));
+
+		match Epd::parse("8/8/8/8/8/8/8/K1k5 x - - bm Kb1;")
+		{
+			Err(EpdError::Position(e)) =>
+				assert_eq!(e.field, FenField::Player),
+			_ => panic!("expected a position error"),
+		}
+	}
+
+	#[test]
+	fn chess960()
+	{
+		let start = |n| Board::chess960(n).unwrap().fen();
+
+		assert_eq!(start(518), Board::default().fen());
+		assert_eq!(start(0), concat!("bbqnnrkr/pppppppp/8/8/8/8/",
+			"PPPPPPPP/BBQNNRKR w KQkq - 0 1"));
+		assert_eq!(start(959), concat!("rkrnnqbb/pppppppp/8/8/8/8/",
+			"PPPPPPPP/RKRNNQBB w KQkq - 0 1"));
+		assert!(Board::chess960(960).is_none());
+
+		let fens = (0..960).map(start)
+			.collect::<std::collections::HashSet<String>>();
+
+		assert_eq!(fens.len(), 960);
+		assert!(fens.iter().all(|f| validate(f) == Ok(())));
+
+		/* Positions from the chessprogramming wiki, with Shredder-FEN
+		 * castling fields */
+		for (fen, nodes) in [
+			(concat!("bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/",
+				"BQ1BNRKR w HFhf - 2 9"), [21, 528, 12189]),
+			(concat!("2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/",
+				"BQNNRBKR w HEhe - 1 9"), [21, 807, 18002]),
+			(concat!("b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/",
+				"BQNNRKRB w GE - 1 9"), [20, 479, 10471]),
+			(concat!("qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/",
+				"QBBNNR1R w hf - 0 9"), [22, 593, 13440]),
+			(concat!("1nbbnrkr/p1p1ppp1/3p4/1p3P1p/3Pq2P/8/PPP1P1P1/",
+				"QNBBNRKR w HFhf - 0 9"), [28, 1120, 31058]),
+		]
+		{
+			let b = Board::from_fen(fen).unwrap();
+
+			assert_eq!([1, 2, 3].map(|d| b.perft(d)), nodes);
+		}
+
+		/* X-FEN writes the file of a rook that is not the outermost */
+		let loc = |s| Loc::parse(s).unwrap();
+		let b = Board::from_fen("4k3/8/8/8/8/8/8/RRK4R w BH - 0 1").unwrap();
+
+		assert_eq!(b.castling(Player::White).q, Some(loc("b1")));
+		assert_eq!(b.fen(), "4k3/8/8/8/8/8/8/RRK4R w KB - 0 1");
+		assert_eq!(Board::from_fen(& b.fen()).unwrap().fen(), b.fen());
+		assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/RRK4R w Q - 0 1")
+			.unwrap().castling(Player::White).q, Some(loc("a1")));
+
+		let after = |fen, san| Board::from_fen(fen).unwrap()
+			.parse_san(san)
+			.map(|m| (Board::from_fen(fen).unwrap().after_move(& m).fen(),
+				m.uci(), m.uci_chess960()));
+
+		/* The king may stay on its square, or take the rook's */
+		assert_eq!(after("4k3/8/8/8/8/8/8/6KR w K - 0 1", "O-O"),
+			Ok((String::from("4k3/8/8/8/8/8/8/5RK1 b - - 1 1"),
+			String::from("g1h1"), String::from("g1h1"))));
+		assert_eq!(after("4k3/8/8/8/8/8/8/5KR1 w K - 0 1", "O-O"),
+			Ok((String::from("4k3/8/8/8/8/8/8/5RK1 b - - 1 1"),
+			String::from("f1g1"), String::from("f1g1"))));
+		assert_eq!(after("4k3/8/8/8/8/8/8/RK6 w Q - 0 1", "O-O-O"),
+			Ok((String::from("4k3/8/8/8/8/8/8/2KR4 b - - 1 1"),
+			String::from("b1c1"), String::from("b1a1"))));
+
+		/* The rook's path must be clear, and the rook may not hide an
+		 * attack on the king's destination */
+		assert_eq!(after("4k3/8/8/8/8/8/8/RK1B4 w Q - 0 1", "O-O-O")
+			.err(), Some(SanError::Illegal));
+		assert_eq!(after("4k3/8/8/8/8/8/8/rR1K4 w Q - 0 1", "O-O-O")
+			.err(), Some(SanError::Illegal));
+	}
 }
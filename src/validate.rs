@@ -0,0 +1,242 @@
+use crate::board::*;
+use crate::piece::*;
+use crate::player::*;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum PositionError
+{
+	KingCount(Player, i32),
+	PawnOnBackRank(Loc),
+	OpponentInCheck,
+	TooManyCheckers(i32),
+	InvalidPassant(Loc),
+	InvalidCastling(Player, Loc),
+	PieceCount(Player),
+}
+
+fn player_name(player: Player) -> & 'static str
+{
+	match player
+	{
+		Player::White => "white",
+		Player::Black => "black",
+	}
+}
+
+impl std::fmt::Display for PositionError
+{
+	fn fmt(self: & Self, f: & mut std::fmt::Formatter) -> std::fmt::Result
+	{
+		match self
+		{
+			Self::KingCount(p, n) => write!(f, "{} has {} kings",
+				player_name(*p), n),
+			Self::PawnOnBackRank(l) => write!(f, "pawn on {}",
+				l.notation(true, true)),
+			Self::OpponentInCheck => write!(f,
+				"the player who does not have the turn is in check"),
+			Self::TooManyCheckers(n) => write!(f,
+				"the king is in check by {} pieces", n),
+			Self::InvalidPassant(l) => write!(f,
+				"no pawn can have passed {}",
+				l.notation(true, true)),
+			Self::InvalidCastling(p, l) => write!(f,
+				"{} can not castle with {}",
+				player_name(*p), l.notation(true, true)),
+			Self::PieceCount(p) => write!(f,
+				"{} has more pieces than promotions allow",
+				player_name(*p)),
+		}
+	}
+}
+
+impl std::error::Error for PositionError {}
+
+fn find_kings(b: & Board, player: Player) -> Vec<Loc>
+{
+	b.locations()
+		.filter(|l| matches!(b.at(*l), Square::Occupied(p)
+				if p.is(player, & KING)))
+		.collect()
+}
+
+/* Count the pieces of the opponent that attack the king of the given
+ * player */
+fn count_checkers(b: & Board, player: Player, king: Loc) -> i32
+{
+	let mut b = *b;
+
+	/* Castling moves can not capture anything */
+	b.set_castling(player, Castling { k: None, q: None });
+	b.set_castling(player.opponent(), Castling { k: None, q: None });
+
+	b.locations()
+		.filter(|l| match b.at(*l)
+		{
+			Square::Occupied(p) if p.is_player(player.opponent())
+				=> p.moves(& b, *l).iter().any(|m| m.to == king),
+			_ => false,
+		})
+		.count() as i32
+}
+
+fn validate_passant(b: & Board, errors: & mut Vec<PositionError>)
+{
+	let loc = match b.passant()
+	{
+		Some(loc) => loc,
+		None => return,
+	};
+
+	/* The opponent must just have moved a pawn two squares, across the
+	 * passant square */
+	let (rank, dir) = match b.player()
+	{
+		Player::White => (5, -1),
+		Player::Black => (2,  1),
+	};
+
+	let valid = loc.valid()
+		&& loc.y == rank
+		&& b.at(loc).empty()
+		&& b.at(loc.offset((0, -dir))).empty()
+		&& matches!(b.at(loc.offset((0, dir))), Square::Occupied(p)
+			if p.is(b.player().opponent(), & PAWN));
+
+	if !valid
+	{
+		errors.push(PositionError::InvalidPassant(loc));
+	}
+}
+
+fn validate_castling(b: & Board, player: Player,
+			errors: & mut Vec<PositionError>)
+{
+	let rank = match player
+	{
+		Player::White => 0,
+		Player::Black => 7,
+	};
+
+	let king = find_kings(b, player).into_iter().find(|l| l.y == rank);
+
+	let c = b.castling(player);
+
+	for (rook, dir) in [(c.k, 1), (c.q, -1)]
+	{
+		let rook = match rook
+		{
+			Some(rook) => rook,
+			None => continue,
+		};
+
+		/* The king and the rook must be on the back rank, with the
+		 * rook on the castling side of the king. The king always
+		 * starts between the rooks, so never in a corner. */
+		let valid = match king
+		{
+			Some(king) => rook.valid()
+				&& king.x > 0 && king.x < 7
+				&& rook.y == rank
+				&& (rook.x - king.x) * dir > 0
+				&& matches!(b.at(rook), Square::Occupied(p)
+					if p.is(player, & ROOK)),
+			None => false,
+		};
+
+		if !valid
+		{
+			errors.push(PositionError::InvalidCastling(player, rook));
+		}
+	}
+}
+
+fn validate_pieces(b: & Board, player: Player,
+			errors: & mut Vec<PositionError>)
+{
+	let count = |kind: & PieceKind| b.locations()
+		.filter(|l| matches!(b.at(*l), Square::Occupied(p)
+				if p.is(player, kind)))
+		.count() as i32;
+
+	let pawns = count(& PAWN);
+
+	/* Every piece in excess of the initial number must have been
+	 * promoted from a pawn */
+	let promoted = (count(& QUEEN) - 1).max(0)
+		+ (count(& ROOK) - 2).max(0)
+		+ (count(& BISHOP) - 2).max(0)
+		+ (count(& KNIGHT) - 2).max(0);
+
+	if pawns + promoted > 8
+	{
+		errors.push(PositionError::PieceCount(player));
+	}
+}
+
+/* Check the board for every violation of the rules that makes the position
+ * impossible to reach in a game */
+pub fn validate_board(b: & Board) -> Result<(), Vec<PositionError>>
+{
+	let mut errors = Vec::<PositionError>::new();
+
+	for player in [Player::White, Player::Black]
+	{
+		let n = find_kings(b, player).len() as i32;
+
+		if n != 1
+		{
+			errors.push(PositionError::KingCount(player, n));
+		}
+	}
+
+	for loc in b.locations()
+	{
+		if (loc.y == 0 || loc.y == 7) && matches!(b.at(loc),
+				Square::Occupied(p) if p.is_kind(& PAWN))
+		{
+			errors.push(PositionError::PawnOnBackRank(loc));
+		}
+	}
+
+	/* Checks can only be judged when each player has a single king */
+	if errors.iter().all(|e| !matches!(e, PositionError::KingCount(..)))
+	{
+		let king = find_kings(b, b.player().opponent())[0];
+
+		if count_checkers(b, b.player().opponent(), king) > 0
+		{
+			errors.push(PositionError::OpponentInCheck);
+		}
+
+		/* No move can give check by more than two pieces */
+		let king = find_kings(b, b.player())[0];
+		let n = count_checkers(b, b.player(), king);
+
+		if n > 2
+		{
+			errors.push(PositionError::TooManyCheckers(n));
+		}
+	}
+
+	validate_passant(b, & mut errors);
+
+	for player in [Player::White, Player::Black]
+	{
+		validate_castling(b, player, & mut errors);
+	}
+
+	for player in [Player::White, Player::Black]
+	{
+		validate_pieces(b, player, & mut errors);
+	}
+
+	if errors.is_empty()
+	{
+		Ok(())
+	}
+	else
+	{
+		Err(errors)
+	}
+}
@@ -0,0 +1,177 @@
+use crate::bitboard::*;
+use crate::board::*;
+use crate::piece::*;
+use crate::player::*;
+
+/* Random keys for each feature of a position. The hash of a position is the
+ * exclusive or of the keys of its features. */
+struct Keys
+{
+	pieces		: [[[u64; 64]; 6]; 2],
+	player		: u64,
+	castling	: [[u64; 8]; 2],
+	passant		: [u64; 8],
+}
+
+/* Generate the keys at compile time with the SplitMix64 generator */
+const fn make_keys() -> Keys
+{
+	let mut state: u64 = 0x5eed_c0ff_ee15_900d;
+	let mut next = [0; 2 * 6 * 64 + 1 + 2 * 8 + 8];
+	let mut i = 0;
+
+	while i < next.len()
+	{
+		state = state.wrapping_add(0x9e3779b97f4a7c15);
+
+		let mut z = state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+		next[i] = z ^ (z >> 31);
+
+		i += 1;
+	}
+
+	let mut keys = Keys
+	{
+		pieces		: [[[0; 64]; 6]; 2],
+		player		: 0,
+		castling	: [[0; 8]; 2],
+		passant		: [0; 8],
+	};
+
+	let mut n = 0;
+
+	i = 0;
+	while i < 2 * 6 * 64
+	{
+		keys.pieces[i / (6 * 64)][i / 64 % 6][i % 64] = next[n];
+		n += 1;
+		i += 1;
+	}
+
+	keys.player = next[n];
+	n += 1;
+
+	i = 0;
+	while i < 2 * 8
+	{
+		keys.castling[i / 8][i % 8] = next[n];
+		n += 1;
+		i += 1;
+	}
+
+	i = 0;
+	while i < 8
+	{
+		keys.passant[i] = next[n];
+		n += 1;
+		i += 1;
+	}
+
+	keys
+}
+
+static KEYS: Keys = make_keys();
+
+/* The key of the given piece standing on the given location */
+pub fn piece_key(p: & Piece, loc: Loc) -> u64
+{
+	let i = (loc.y * 8 + loc.x) as usize;
+
+	KEYS.pieces[p.player as usize][p.kind.index][i]
+}
+
+/* The key of the player who has the turn, when that is black */
+pub fn player_key() -> u64
+{
+	KEYS.player
+}
+
+/* The key of the castling rights of both players */
+pub fn castling_key(castling: & [Castling; 2]) -> u64
+{
+	let mut key = 0;
+
+	for (player, c) in castling.iter().enumerate()
+	{
+		for rook in [c.k, c.q].into_iter().flatten()
+		{
+			key ^= KEYS.castling[player][rook.x as usize];
+		}
+	}
+
+	key
+}
+
+/* The key of the passant file, which is only included if a pawn of the
+ * player who has the turn stands ready to capture */
+pub fn passant_key(b: & Board) -> u64
+{
+	match b.passant()
+	{
+		Some(passant) if pawn_attacks(b.player().opponent(), passant)
+				& b.bitboard(b.player(), & PAWN) != 0
+			=> KEYS.passant[passant.x as usize],
+		_ => 0,
+	}
+}
+
+/* Compute the hash of the board from scratch, from the squares and the
+ * state of the board, independently of the keys above that Board::make_move
+ * updates the hash with */
+pub fn zobrist_hash(b: & Board) -> u64
+{
+	let mut key = 0;
+
+	if b.player() == Player::Black
+	{
+		key ^= KEYS.player;
+	}
+
+	for player in [Player::White, Player::Black]
+	{
+		let c = b.castling(player);
+
+		if let Some(rook) = c.k
+		{
+			key ^= KEYS.castling[player as usize][rook.x as usize];
+		}
+
+		if let Some(rook) = c.q
+		{
+			key ^= KEYS.castling[player as usize][rook.x as usize];
+		}
+	}
+
+	if let Some(passant) = b.passant()
+	{
+		/* The pawns that could capture stand next to the passant file,
+		 * on the rank the passant pawn moved to */
+		let y = match b.player()
+		{
+			Player::White => passant.y - 1,
+			Player::Black => passant.y + 1,
+		};
+
+		let capturable = [-1, 1].into_iter()
+			.map(|dx| Loc { x: passant.x + dx, y })
+			.any(|l| l.valid() && matches!(b.at(l),
+				Square::Occupied(p) if p.is(b.player(), & PAWN)));
+
+		if capturable
+		{
+			key ^= KEYS.passant[passant.x as usize];
+		}
+	}
+
+	for loc in b.locations()
+	{
+		if let Square::Occupied(p) = b.at(loc)
+		{
+			key ^= piece_key(p, loc);
+		}
+	}
+
+	key
+}